@@ -1,4 +1,82 @@
 // build.rs
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Headers defining keysym names, and the prefix of their macros, which stands in for the ```XK_```
+/// of each name (eg. ```XF86XK_AudioMute``` is named ```XF86AudioMute```), as in Xlib's own table.
+const KEYSYM_HEADERS: &[(&str, &str, &str)] = &[
+    ("keysymdef.h", "XK_", ""),
+    ("XF86keysym.h", "XF86XK_", "XF86"),
+];
+
+/// Value of ```_EVDEVK(0)``` (XF86keysym.h).
+const EVDEVK_OFFSET: u32 = 0x10081000;
+
 fn main() {
     println!("cargo:rustc-link-lib=X11");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=X11_INCLUDE_DIR");
+
+    let include = env::var_os("X11_INCLUDE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/usr/include/X11"));
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("keysyms.rs");
+    fs::write(out, keysym_table(&include)).unwrap();
+}
+
+/// Generates ```KEYSYMS```, every keysym name and value, sorted by name, so that key names may be
+/// looked up without Xlib.
+fn keysym_table(include: &Path) -> String {
+    let mut keysyms: Vec<(String, u32)> = Vec::new();
+
+    for (header, prefix, name_prefix) in KEYSYM_HEADERS {
+        let path = include.join(header);
+        println!("cargo:rerun-if-changed={}", path.display());
+        let contents = fs::read_to_string(&path).unwrap_or_else(|err| {
+            panic!(
+                "Could not read {} ({}); set X11_INCLUDE_DIR to the directory of the X11 headers",
+                path.display(),
+                err
+            )
+        });
+
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            if words.next() != Some("#define") {
+                continue;
+            }
+            let (name, value) = match (words.next(), words.next()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => continue,
+            };
+            let name = match name.strip_prefix(prefix) {
+                Some(name) => format!("{}{}", name_prefix, name),
+                None => continue,
+            };
+            /* Keysyms for evdev keycodes are offset by _EVDEVK, eg. _EVDEVK(0x0f4) */
+            let (value, offset) = match value.strip_prefix("_EVDEVK(") {
+                Some(value) => (value.trim_end_matches(')'), EVDEVK_OFFSET),
+                None => (value, 0),
+            };
+            if let Some(value) = value
+                .strip_prefix("0x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            {
+                keysyms.push((name, offset + value));
+            }
+        }
+    }
+
+    keysyms.sort();
+    keysyms.dedup_by(|a, b| a.0 == b.0);
+
+    let mut table =
+        String::from("/// Every keysym name (without ```XK_```) and value, sorted by name.\n");
+    table.push_str("const KEYSYMS: &[(&str, Keysym)] = &[\n");
+    for (name, value) in keysyms {
+        table.push_str(&format!("    ({:?}, {:#x}),\n", name, value));
+    }
+    table.push_str("];\n");
+    table
 }
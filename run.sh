@@ -10,25 +10,30 @@ function build {
 }
 
 function start {
-	case $args in
-		-i|--info) # operational info
-			echo info
-			log="info"
-			;;
-		-d|--debug) # debug level
-			echo debug
-			log="debug"
-			;;
-		-t|--trace) # trace level
-			echo trace
-			log="trace"
-			;;
-		*)			# default 
-			log="info,debug,trace"
-			;;
-	esac
+	log="info,debug,trace"	# default
+	screens="-screen 1920x1080"
+	for arg in $args; do
+		case $arg in
+			-i|--info) # operational info
+				echo info
+				log="info"
+				;;
+			-d|--debug) # debug level
+				echo debug
+				log="debug"
+				;;
+			-t|--trace) # trace level
+				echo trace
+				log="trace"
+				;;
+			-m|--multihead) # two side by side outputs, through Xinerama
+				echo multihead
+				screens="+xinerama -screen 960x1080 -screen 960x1080"
+				;;
+		esac
+	done
 
-	Xephyr -br -ac -noreset -screen 1920x1080 :3 &
+	Xephyr -br -ac -noreset $screens :3 &
 	sleep 1
 	DISPLAY=:3 RUST_LOG=$log exec "/home/blinklad/dev/rust/rdwm/target/debug/rdwm" &
 	sleep 1
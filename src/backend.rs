@@ -0,0 +1,1353 @@
+//! The operations Rdwm performs on the display server, behind a ```Backend``` trait so that the
+//! window management core (```Rdwm```'s workspaces and clients) is agnostic of the bindings used,
+//! eg. Xlib or XCB.
+//! Window ids, atoms, keysyms, masks and pixels are core protocol values, the same for any binding;
+//! events are decoded by the backend into an ```Event```.
+//! The Xlib backend also discovers outputs (```output```) and takes the manager selection
+//! (```selection```), through Xlib specific extensions and calls.
+use super::keys::KeysymNames;
+use super::layout::Quad;
+use super::output::Outputs;
+use super::rdwm::StartupError;
+use super::selection::ManagerSelection;
+use super::xerror::XError;
+use libc::*;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::fmt;
+use std::sync::Mutex;
+use x11::xlib::*;
+
+/// A window, as identified by the X server.
+pub type WindowId = u32;
+/// An interned name, eg. of a property or protocol.
+pub type Atom = u32;
+/// A symbol on a key, eg. ```Return```, whatever the keyboard's layout.
+pub type Keysym = u32;
+/// A physical key of the current keyboard.
+pub type Keycode = u8;
+
+/* Core protocol values (X.h), which every binding shares */
+
+/// Modifier masks, as in a key event's state.
+pub const SHIFT_MASK: c_uint = 1 << 0;
+pub const LOCK_MASK: c_uint = 1 << 1;
+pub const CONTROL_MASK: c_uint = 1 << 2;
+pub const MOD1_MASK: c_uint = 1 << 3;
+pub const MOD2_MASK: c_uint = 1 << 4;
+pub const MOD3_MASK: c_uint = 1 << 5;
+pub const MOD4_MASK: c_uint = 1 << 6;
+pub const MOD5_MASK: c_uint = 1 << 7;
+
+/// Event masks, for ```select_input```.
+pub const ENTER_WINDOW_MASK: c_long = 1 << 4;
+pub const LEAVE_WINDOW_MASK: c_long = 1 << 5;
+pub const SUBSTRUCTURE_NOTIFY_MASK: c_long = 1 << 19;
+pub const SUBSTRUCTURE_REDIRECT_MASK: c_long = 1 << 20;
+pub const FOCUS_CHANGE_MASK: c_long = 1 << 21;
+
+/// Value masks, selecting the ```WindowChanges``` that apply.
+pub const CW_X: c_uint = 1 << 0;
+pub const CW_Y: c_uint = 1 << 1;
+pub const CW_WIDTH: c_uint = 1 << 2;
+pub const CW_HEIGHT: c_uint = 1 << 3;
+pub const CW_SIBLING: c_uint = 1 << 5;
+pub const CW_STACK_MODE: c_uint = 1 << 6;
+
+/// The primary (usually left) pointer button.
+pub const BUTTON1: c_uint = 1;
+/// Stands in for a timestamp, meaning the server's current time.
+pub const CURRENT_TIME: c_long = 0;
+
+/// XKB constants (XKB.h), which Xlib does not define.
+const XKB_MAJOR_VERSION: c_int = 1;
+const XKB_MINOR_VERSION: c_int = 0;
+const XKB_USE_CORE_KBD: c_uint = 0x0100;
+const XKB_NEW_KEYBOARD_NOTIFY_MASK: c_ulong = 1 << 0;
+const XKB_MAP_NOTIFY_MASK: c_ulong = 1 << 1;
+
+lazy_static! {
+    /// Lazily evaluated Mutex used to guard global error state required by Xlib error handler registration.
+    /// It's not an ideal way to handle global state (even if it was changed to a more performant RefCell
+    /// but will do for the time being.
+    static ref WM_DETECTED: Mutex<bool> = Mutex::new(false);
+}
+
+/// Whether a window is mapped, and if so whether it (and every ancestor) is, ie. it is viewable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapState {
+    Unmapped,
+    Unviewable,
+    Viewable,
+}
+
+/// The attributes of an existing window that Rdwm cares about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowAttributes {
+    /// Position relative to the parent window, and size, excluding the border.
+    pub area: Quad,
+    pub border_width: u32,
+    /// Set on windows that ask not to be managed, eg. menus and tooltips.
+    pub override_redirect: bool,
+    pub map_state: MapState,
+}
+
+/// An event reported by the server, decoded into what Rdwm needs of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    KeyPress {
+        keycode: Keycode,
+        state: c_uint,
+    },
+    ButtonPress {
+        window: WindowId,
+    },
+    EnterNotify {
+        window: WindowId,
+    },
+    LeaveNotify {
+        window: WindowId,
+    },
+    FocusIn {
+        window: WindowId,
+    },
+    FocusOut {
+        window: WindowId,
+    },
+    CreateNotify {
+        window: WindowId,
+    },
+    DestroyNotify {
+        window: WindowId,
+    },
+    /// ```synthetic``` events were sent by a client, eg. on withdrawing an unmapped window.
+    UnmapNotify {
+        window: WindowId,
+        synthetic: bool,
+    },
+    MapNotify {
+        window: WindowId,
+    },
+    MapRequest {
+        window: WindowId,
+    },
+    ReparentNotify {
+        window: WindowId,
+        parent: WindowId,
+    },
+    ConfigureNotify {
+        window: WindowId,
+    },
+    /// The changes a client asks for, of which only those in ```mask``` apply.
+    ConfigureRequest {
+        window: WindowId,
+        changes: WindowChanges,
+        mask: c_uint,
+    },
+    PropertyNotify {
+        window: WindowId,
+        atom: Atom,
+    },
+    /// A 32 bit client message.
+    ClientMessage {
+        window: WindowId,
+        message_type: Atom,
+        data: [c_long; 5],
+    },
+    SelectionClear {
+        window: WindowId,
+        selection: Atom,
+    },
+    /// The keyboard, or the keys its keysyms or modifiers are on, changed.
+    KeyboardChanged,
+    /// Outputs were added, removed or reconfigured.
+    ScreenChange,
+    /// An event of no interest to Rdwm, eg. ```Expose```.
+    Ignored,
+}
+
+/// Changes requested of a window's geometry or stacking, of which only those in the accompanying
+/// value mask (eg. ```CW_X | CW_WIDTH```) apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowChanges {
+    pub x: c_int,
+    pub y: c_int,
+    pub width: c_int,
+    pub height: c_int,
+    pub border_width: c_int,
+    pub sibling: WindowId,
+    pub stack_mode: c_int,
+}
+
+/// Looks up keysyms by name, as in ```keysymdef.h``` without the ```XK_``` prefix.
+/// Needs no connection, so that key names may be checked without a display.
+pub trait Keysyms {
+    fn keysym(&self, name: &str) -> Option<Keysym>;
+}
+
+/// Everything Rdwm asks of the display server once connected.
+/// Requests are buffered, as in Xlib: they are sent when events are next read, or on ```sync```.
+pub trait Backend: Keysyms + fmt::Debug {
+    /// The root window of the managed screen.
+    fn root(&self) -> WindowId;
+    /// Takes the ICCCM manager selection, announcing Rdwm as the screen's window manager. With
+    /// ```replace```, a window manager that owns it is asked to exit, and waited for.
+    fn acquire_selection(&self, replace: bool) -> Result<ManagerSelection, StartupError>;
+    /// Selects substructure redirection, and the other events Rdwm needs, on the root window.
+    /// Only one X client may redirect the root window's substructure, so returns false if another
+    /// window manager already has.
+    fn redirect_root(&self) -> bool;
+    /// The geometry of every active output, eg. monitor, left to right. Falls back to the whole
+    /// screen if outputs cannot be told apart.
+    fn outputs(&self) -> Vec<Quad>;
+
+    /* Windows */
+
+    /// Creates an unmapped window, eg. a client's frame.
+    fn create_window(
+        &self,
+        parent: WindowId,
+        area: Quad,
+        border_width: u32,
+        pixel: c_ulong,
+    ) -> WindowId;
+    fn destroy_window(&self, window: WindowId);
+    /// Moves a window into another, at the given position relative to its new parent.
+    fn reparent(&self, window: WindowId, parent: WindowId, x: c_int, y: c_int);
+    fn map(&self, window: WindowId);
+    fn unmap(&self, window: WindowId);
+    fn move_resize(&self, window: WindowId, area: Quad);
+    /// Applies the changes selected by ```mask```, eg. as requested by a client.
+    fn configure(&self, window: WindowId, changes: &WindowChanges, mask: c_uint);
+    /// Stacks windows from top to bottom in the given order.
+    fn restack(&self, windows: &[WindowId]);
+    fn set_border_width(&self, window: WindowId, width: u32);
+    fn set_border_pixel(&self, window: WindowId, pixel: c_ulong);
+    /// Returns ```None``` if the window does not exist (anymore).
+    fn window_attributes(&self, window: WindowId) -> Option<WindowAttributes>;
+    /// Adds a client window to those the server restores, should Rdwm exit without doing so.
+    fn add_to_save_set(&self, window: WindowId);
+    fn remove_from_save_set(&self, window: WindowId);
+    /// Disconnects the client that created the window, destroying all of its windows.
+    fn kill_client(&self, window: WindowId);
+
+    /* Input */
+
+    /// Selects the events (eg. ```ENTER_WINDOW_MASK```) to report for a window.
+    fn select_input(&self, window: WindowId, mask: c_long);
+    fn focus(&self, window: WindowId);
+    fn grab_button(&self, window: WindowId, button: c_uint, modifiers: c_uint);
+    /// Returns ```None``` if the keysym is not on the current keyboard.
+    fn keycode(&self, keysym: Keysym) -> Option<Keycode>;
+    /// Grabs a key on a window, for the given combination of modifiers exactly.
+    fn grab_key(&self, window: WindowId, keycode: Keycode, modifiers: c_uint);
+    /// Releases every key grabbed on a window.
+    fn ungrab_keys(&self, window: WindowId);
+    /// The modifier mask that Num Lock is currently mapped onto, if any (usually ```MOD2_MASK```).
+    fn numlock_mask(&self) -> c_uint;
+
+    /* Colours */
+
+    /// Allocates the closest colour to a ```0xrrggbb``` value that the default colormap supports.
+    /// Returns ```None``` if the colormap is full.
+    fn alloc_colour(&self, rgb: c_ulong) -> Option<c_ulong>;
+    fn free_colours(&self, pixels: &[c_ulong]);
+    fn black_pixel(&self) -> c_ulong;
+
+    /* Properties */
+
+    fn intern_atom(&self, name: &str) -> Atom;
+    /// Replaces a property with 32 bit values.
+    fn set_property(&self, window: WindowId, property: Atom, kind: Atom, data: &[c_long]);
+    /// Replaces a property with text, eg. of type ```UTF8_STRING```.
+    fn set_text_property(&self, window: WindowId, property: Atom, kind: Atom, text: &str);
+    /// The atoms a client lists in its ```WM_PROTOCOLS``` property.
+    fn protocols(&self, window: WindowId) -> Vec<Atom>;
+    /// Tells a client its geometry, in root coordinates, with a synthetic ```ConfigureNotify```, as
+    /// ICCCM requires when a ```ConfigureRequest``` is refused or the client's frame moves.
+    fn send_configure_notify(&self, window: WindowId, area: Quad, border_width: u32);
+    /// Sends a client a 32 bit client message, eg. ```WM_PROTOCOLS```, of up to five values.
+    fn send_client_message(&self, window: WindowId, message_type: Atom, data: &[c_long]);
+
+    /* Server & events */
+
+    /// The top-level windows that existed before Rdwm started, from bottom to top.
+    fn existing_windows(&self) -> Option<Vec<WindowId>>;
+    /// Stops the server processing requests from any other client, until ```ungrab_server```.
+    fn grab_server(&self);
+    fn ungrab_server(&self);
+    /// Sends buffered requests, and waits until the server has processed them.
+    fn sync(&self);
+    /// Sends buffered requests, then returns whether any event is queued.
+    fn pending(&self) -> bool;
+    /// Returns the next event, blocking until one arrives. The backend's own state, eg. its copy of
+    /// the keyboard mapping, is updated before the event is returned.
+    fn next_event(&self) -> Event;
+    /// The file descriptor of the connection, which is readable when events arrive.
+    fn connection(&self) -> c_int;
+}
+
+/// Decodes the events of an extension, eg. RandR, whose event types are only known at runtime.
+type ExtensionDecoder = fn(&XlibBackend, &mut XEvent) -> Event;
+
+/// The Xlib backend: a display connection, closed on drop.
+#[derive(Debug)]
+pub struct XlibBackend {
+    display: *mut Display,
+    root: Window,
+    colormap: Colormap,
+    outputs: Outputs,
+    extensions: Vec<(c_int, ExtensionDecoder)>,
+    /// Event types already logged as unknown, so that each is only logged once.
+    unknown_events: RefCell<HashSet<c_int>>,
+}
+
+impl XlibBackend {
+    /// Connects to the display named by ```$DISPLAY```, with an error handler that logs (rather than
+    /// exits on) errors, then loads the extensions that outputs and keyboard changes are reported
+    /// through.
+    pub fn open() -> Result<Self, StartupError> {
+        let display = unsafe {
+            /* Safe because no side effects at this point */
+            XOpenDisplay(std::ptr::null())
+        };
+
+        if display.is_null() {
+            return Err(StartupError::NoDisplay);
+        }
+
+        let mut backend = unsafe {
+            XSetErrorHandler(Some(on_error));
+            if XScreenOfDisplay(display, 0).is_null() {
+                trace!("No screens associated with display");
+                XCloseDisplay(display);
+                return Err(StartupError::NoScreen);
+            }
+
+            /* Safe as the display is open, and has a default screen */
+            let root = XDefaultRootWindow(display);
+            XlibBackend {
+                display,
+                root,
+                colormap: XDefaultColormap(display, XDefaultScreen(display)),
+                outputs: Outputs::open(display, root),
+                extensions: Vec::new(),
+                unknown_events: RefCell::default(),
+            }
+        };
+        backend.register_extensions();
+
+        Ok(backend)
+    }
+
+    /// Registers decoders for RandR output changes and, where available, XKB keyboard changes.
+    fn register_extensions(&mut self) {
+        if let Some(event_type) = self.outputs.screen_change_event() {
+            self.extensions
+                .push((event_type, XlibBackend::decode_screen_change));
+        }
+
+        if let Some(event_type) = self.select_keyboard_events() {
+            self.extensions.push((event_type, XlibBackend::decode_xkb));
+        }
+    }
+
+    /// Selects XKB notifications of keyboard (mapping) changes, returning the type of XKB events, if
+    /// the server supports XKB.
+    fn select_keyboard_events(&self) -> Option<c_int> {
+        let (mut opcode, mut event_base, mut error_base) = (0, 0, 0);
+        let (mut major, mut minor) = (XKB_MAJOR_VERSION, XKB_MINOR_VERSION);
+        unsafe {
+            if XkbQueryExtension(
+                self.display,
+                &mut opcode,
+                &mut event_base,
+                &mut error_base,
+                &mut major,
+                &mut minor,
+            ) == 0
+            {
+                return None;
+            }
+
+            /* Every XKB event has the same type, and an XKB specific subtype */
+            let mask = XKB_NEW_KEYBOARD_NOTIFY_MASK | XKB_MAP_NOTIFY_MASK;
+            XkbSelectEvents(self.display, XKB_USE_CORE_KBD, mask, mask);
+        }
+        Some(event_base)
+    }
+
+    /// Decodes an event. Every core event type is either decoded or deliberately ignored; extension
+    /// events go to the decoder registered for their type, and any other event is logged (once per
+    /// type) and ignored.
+    fn decode(&self, event: &mut XEvent) -> Event {
+        #[allow(non_upper_case_globals)]
+        /* Safe because we know that the type of event dictates well-defined union member access */
+        unsafe {
+            match event.get_type() {
+                KeyPress => Event::KeyPress {
+                    keycode: event.key.keycode as Keycode,
+                    state: event.key.state,
+                },
+                ButtonPress => Event::ButtonPress {
+                    window: event.button.window as WindowId,
+                },
+                EnterNotify => Event::EnterNotify {
+                    window: event.crossing.window as WindowId,
+                },
+                LeaveNotify => Event::LeaveNotify {
+                    window: event.crossing.window as WindowId,
+                },
+                FocusIn => Event::FocusIn {
+                    window: event.focus_change.window as WindowId,
+                },
+                FocusOut => Event::FocusOut {
+                    window: event.focus_change.window as WindowId,
+                },
+                CreateNotify => Event::CreateNotify {
+                    window: event.create_window.window as WindowId,
+                },
+                DestroyNotify => Event::DestroyNotify {
+                    window: event.destroy_window.window as WindowId,
+                },
+                UnmapNotify => Event::UnmapNotify {
+                    window: event.unmap.window as WindowId,
+                    synthetic: event.unmap.send_event != 0,
+                },
+                MapNotify => Event::MapNotify {
+                    window: event.map.window as WindowId,
+                },
+                MapRequest => Event::MapRequest {
+                    window: event.map_request.window as WindowId,
+                },
+                ReparentNotify => Event::ReparentNotify {
+                    window: event.reparent.window as WindowId,
+                    parent: event.reparent.parent as WindowId,
+                },
+                ConfigureNotify => Event::ConfigureNotify {
+                    window: event.configure.window as WindowId,
+                },
+                ConfigureRequest => {
+                    let request = &event.configure_request;
+                    Event::ConfigureRequest {
+                        window: request.window as WindowId,
+                        changes: WindowChanges {
+                            x: request.x,
+                            y: request.y,
+                            width: request.width,
+                            height: request.height,
+                            border_width: request.border_width,
+                            sibling: request.above as WindowId,
+                            stack_mode: request.detail,
+                        },
+                        mask: request.value_mask as c_uint,
+                    }
+                }
+                PropertyNotify => Event::PropertyNotify {
+                    window: event.property.window as WindowId,
+                    atom: event.property.atom as Atom,
+                },
+                ClientMessage => {
+                    let message = &event.client_message;
+                    let mut data = [0; 5];
+                    for (i, value) in data.iter_mut().enumerate() {
+                        *value = message.data.get_long(i);
+                    }
+                    Event::ClientMessage {
+                        window: message.window as WindowId,
+                        message_type: message.message_type as Atom,
+                        data,
+                    }
+                }
+                SelectionClear => Event::SelectionClear {
+                    window: event.selection_clear.window as WindowId,
+                    selection: event.selection_clear.selection as Atom,
+                },
+                MappingNotify => {
+                    /* Keycodes are looked up in Xlib's copy of the keyboard mapping */
+                    XRefreshKeyboardMapping(&mut event.mapping);
+                    match event.mapping.request {
+                        MappingKeyboard | MappingModifier => Event::KeyboardChanged,
+                        _ => Event::Ignored,
+                    }
+                }
+                /* Not selected, or of no interest to a window manager without decorations */
+                KeyRelease | ButtonRelease | MotionNotify | KeymapNotify | Expose
+                | GraphicsExpose | NoExpose | VisibilityNotify | GravityNotify | ResizeRequest
+                | CirculateNotify | CirculateRequest | SelectionRequest | SelectionNotify
+                | ColormapNotify | GenericEvent => {
+                    trace!("Ignoring event: {:#?}", *event);
+                    Event::Ignored
+                }
+                other => {
+                    let decoder = self
+                        .extensions
+                        .iter()
+                        .find(|(event_type, _)| *event_type == other)
+                        .map(|(_, decoder)| *decoder);
+                    match decoder {
+                        Some(decoder) => decoder(self, event),
+                        None => {
+                            if self.unknown_events.borrow_mut().insert(other) {
+                                debug!("Ignoring events of unknown type {}", other);
+                            }
+                            Event::Ignored
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Updates Xlib's idea of the screen size after RandR reports that outputs changed.
+    fn decode_screen_change(&self, event: &mut XEvent) -> Event {
+        self.outputs.update(event);
+        Event::ScreenChange
+    }
+
+    /// Reports changes to the keyboard, or its mapping.
+    fn decode_xkb(&self, event: &mut XEvent) -> Event {
+        /* Safe as every XKB event starts with the fields of XkbAnyEvent */
+        let xkb = unsafe { &*(event as *const XEvent as *const XkbAnyEvent) };
+        trace!("XKB event: {:#?}", xkb);
+
+        if xkb.xkb_type == XkbNewKeyboardNotify || xkb.xkb_type == XkbMapNotify {
+            Event::KeyboardChanged
+        } else {
+            Event::Ignored
+        }
+    }
+}
+
+impl Drop for XlibBackend {
+    /// Ensure that when event loop is exited through well-defined behaviour (eg. stack unwinding,
+    /// normal exit or X server requests) that the display handle is closed.
+    fn drop(&mut self) {
+        unsafe {
+            /* Safe because only 1 WM per x server */
+            XCloseDisplay(self.display);
+            info!("Closed display OK");
+        }
+    }
+}
+
+impl Backend for XlibBackend {
+    fn root(&self) -> WindowId {
+        self.root as WindowId
+    }
+
+    fn acquire_selection(&self, replace: bool) -> Result<ManagerSelection, StartupError> {
+        ManagerSelection::acquire(self.display, self.root, replace)
+    }
+
+    fn redirect_root(&self) -> bool {
+        unsafe {
+            XSetErrorHandler(Some(on_startup_error));
+
+            XSelectInput(
+                self.display,
+                self.root,
+                KeyPressMask | SubstructureRedirectMask | SubstructureNotifyMask | FocusChangeMask,
+            );
+
+            /* Any BadAccess error is reported to the startup handler before XSync returns */
+            XSync(self.display, false as c_int);
+            XSetErrorHandler(Some(on_error));
+        }
+
+        !std::mem::take(&mut *WM_DETECTED.lock().unwrap())
+    }
+
+    fn outputs(&self) -> Vec<Quad> {
+        self.outputs.query(self.display, self.root)
+    }
+
+    fn create_window(
+        &self,
+        parent: WindowId,
+        area: Quad,
+        border_width: u32,
+        pixel: c_ulong,
+    ) -> WindowId {
+        unsafe {
+            XCreateSimpleWindow(
+                self.display,
+                parent as Window,
+                area.x as c_int,
+                area.y as c_int,
+                area.w.max(1),
+                area.h.max(1),
+                border_width,
+                pixel,
+                pixel,
+            ) as WindowId
+        }
+    }
+
+    fn destroy_window(&self, window: WindowId) {
+        unsafe {
+            XDestroyWindow(self.display, window as Window);
+        }
+    }
+
+    fn reparent(&self, window: WindowId, parent: WindowId, x: c_int, y: c_int) {
+        unsafe {
+            XReparentWindow(self.display, window as Window, parent as Window, x, y);
+        }
+    }
+
+    fn map(&self, window: WindowId) {
+        unsafe {
+            XMapWindow(self.display, window as Window);
+        }
+    }
+
+    fn unmap(&self, window: WindowId) {
+        unsafe {
+            XUnmapWindow(self.display, window as Window);
+        }
+    }
+
+    fn move_resize(&self, window: WindowId, area: Quad) {
+        unsafe {
+            XMoveResizeWindow(
+                self.display,
+                window as Window,
+                area.x as c_int,
+                area.y as c_int,
+                area.w,
+                area.h,
+            );
+        }
+    }
+
+    fn configure(&self, window: WindowId, changes: &WindowChanges, mask: c_uint) {
+        let mut changes = XWindowChanges {
+            x: changes.x,
+            y: changes.y,
+            width: changes.width,
+            height: changes.height,
+            border_width: changes.border_width,
+            sibling: changes.sibling as Window,
+            stack_mode: changes.stack_mode,
+        };
+        unsafe {
+            XConfigureWindow(self.display, window as Window, mask, &mut changes);
+        }
+    }
+
+    fn restack(&self, windows: &[WindowId]) {
+        let mut windows: Vec<Window> = windows.iter().map(|w| *w as Window).collect();
+        if !windows.is_empty() {
+            unsafe {
+                XRestackWindows(self.display, windows.as_mut_ptr(), windows.len() as c_int);
+            }
+        }
+    }
+
+    fn set_border_width(&self, window: WindowId, width: u32) {
+        unsafe {
+            XSetWindowBorderWidth(self.display, window as Window, width);
+        }
+    }
+
+    fn set_border_pixel(&self, window: WindowId, pixel: c_ulong) {
+        unsafe {
+            XSetWindowBorder(self.display, window as Window, pixel);
+        }
+    }
+
+    fn window_attributes(&self, window: WindowId) -> Option<WindowAttributes> {
+        /* Safe as XGetWindowAttributes writes to attrs on success */
+        let attrs = unsafe {
+            let mut attrs = std::mem::MaybeUninit::<XWindowAttributes>::zeroed().assume_init();
+            if XGetWindowAttributes(self.display, window as Window, &mut attrs) == 0 {
+                return None;
+            }
+            attrs
+        };
+
+        #[allow(non_upper_case_globals)]
+        Some(WindowAttributes {
+            area: Quad {
+                x: attrs.x.max(0) as u32,
+                y: attrs.y.max(0) as u32,
+                w: attrs.width.max(0) as u32,
+                h: attrs.height.max(0) as u32,
+            },
+            border_width: attrs.border_width.max(0) as u32,
+            override_redirect: attrs.override_redirect != 0,
+            map_state: match attrs.map_state {
+                IsViewable => MapState::Viewable,
+                IsUnviewable => MapState::Unviewable,
+                _ => MapState::Unmapped,
+            },
+        })
+    }
+
+    fn add_to_save_set(&self, window: WindowId) {
+        unsafe {
+            XAddToSaveSet(self.display, window as Window);
+        }
+    }
+
+    fn remove_from_save_set(&self, window: WindowId) {
+        unsafe {
+            XRemoveFromSaveSet(self.display, window as Window);
+        }
+    }
+
+    fn kill_client(&self, window: WindowId) {
+        unsafe {
+            XKillClient(self.display, window as Window);
+        }
+    }
+
+    fn select_input(&self, window: WindowId, mask: c_long) {
+        unsafe {
+            XSelectInput(self.display, window as Window, mask);
+        }
+    }
+
+    fn focus(&self, window: WindowId) {
+        unsafe {
+            XSetInputFocus(
+                self.display,
+                window as Window,
+                RevertToPointerRoot,
+                CurrentTime,
+            );
+        }
+    }
+
+    fn grab_button(&self, window: WindowId, button: c_uint, modifiers: c_uint) {
+        unsafe {
+            XGrabButton(
+                self.display,
+                button,
+                modifiers,
+                window as Window,
+                false as c_int,
+                0,
+                GrabModeSync,
+                GrabModeSync,
+                window as Window,
+                0,
+            );
+        }
+    }
+
+    fn keycode(&self, keysym: Keysym) -> Option<Keycode> {
+        /* Safe as XKeysymToKeycode only reads the display's keyboard mapping */
+        match unsafe { XKeysymToKeycode(self.display, keysym as KeySym) } {
+            0 => None,
+            keycode => Some(keycode),
+        }
+    }
+
+    fn grab_key(&self, window: WindowId, keycode: Keycode, modifiers: c_uint) {
+        unsafe {
+            XGrabKey(
+                self.display,
+                keycode as c_int,
+                modifiers,
+                window as Window,
+                true as c_int,
+                GrabModeAsync,
+                GrabModeAsync,
+            );
+        }
+    }
+
+    fn ungrab_keys(&self, window: WindowId) {
+        unsafe {
+            XUngrabKey(self.display, AnyKey, AnyModifier, window as Window);
+        }
+    }
+
+    fn numlock_mask(&self) -> c_uint {
+        let mut mask = 0;
+
+        unsafe {
+            /* Safe as the modifier map is max_keypermod keycodes for each of the 8 modifiers */
+            let modmap = XGetModifierMapping(self.display);
+            if modmap.is_null() {
+                return 0;
+            }
+
+            let numlock = XKeysymToKeycode(self.display, x11::keysym::XK_Num_Lock as KeySym);
+            let per_modifier = (*modmap).max_keypermod as usize;
+            let keycodes = std::slice::from_raw_parts((*modmap).modifiermap, 8 * per_modifier);
+
+            for (modifier, codes) in keycodes.chunks(per_modifier.max(1)).enumerate() {
+                if numlock != 0 && codes.contains(&numlock) {
+                    mask = 1 << modifier;
+                }
+            }
+
+            XFreeModifiermap(modmap);
+        }
+
+        mask
+    }
+
+    fn alloc_colour(&self, rgb: c_ulong) -> Option<c_ulong> {
+        /* X colours use 16 bits per channel; scale each 8 bit channel by 0x101 */
+        let mut colour = XColor {
+            pixel: 0,
+            red: ((rgb >> 16 & 0xff) * 0x101) as c_ushort,
+            green: ((rgb >> 8 & 0xff) * 0x101) as c_ushort,
+            blue: ((rgb & 0xff) * 0x101) as c_ushort,
+            flags: (DoRed | DoGreen | DoBlue) as c_char,
+            pad: 0,
+        };
+
+        match unsafe { XAllocColor(self.display, self.colormap, &mut colour) } {
+            0 => None,
+            _ => Some(colour.pixel),
+        }
+    }
+
+    fn free_colours(&self, pixels: &[c_ulong]) {
+        let mut pixels = pixels.to_vec();
+        if !pixels.is_empty() {
+            unsafe {
+                XFreeColors(
+                    self.display,
+                    self.colormap,
+                    pixels.as_mut_ptr(),
+                    pixels.len() as c_int,
+                    0,
+                );
+            }
+        }
+    }
+
+    fn black_pixel(&self) -> c_ulong {
+        unsafe { XBlackPixel(self.display, XDefaultScreen(self.display)) }
+    }
+
+    fn intern_atom(&self, name: &str) -> Atom {
+        let name = CString::new(name).expect("Atom names contain no NUL bytes");
+        /* Safe as the name outlives the call, and the display is open */
+        unsafe { XInternAtom(self.display, name.as_ptr(), false as c_int) as Atom }
+    }
+
+    fn set_property(&self, window: WindowId, property: Atom, kind: Atom, data: &[c_long]) {
+        unsafe {
+            XChangeProperty(
+                self.display,
+                window as Window,
+                property as c_ulong,
+                kind as c_ulong,
+                32,
+                PropModeReplace,
+                data.as_ptr() as *const c_uchar,
+                data.len() as c_int,
+            );
+        }
+    }
+
+    fn set_text_property(&self, window: WindowId, property: Atom, kind: Atom, text: &str) {
+        unsafe {
+            XChangeProperty(
+                self.display,
+                window as Window,
+                property as c_ulong,
+                kind as c_ulong,
+                8,
+                PropModeReplace,
+                text.as_ptr(),
+                text.len() as c_int,
+            );
+        }
+    }
+
+    fn protocols(&self, window: WindowId) -> Vec<Atom> {
+        unsafe {
+            /* Safe as XGetWMProtocols returns count atoms on success, freed with XFree */
+            let mut protocols: *mut c_ulong = std::ptr::null_mut();
+            let mut count = 0;
+            if XGetWMProtocols(self.display, window as Window, &mut protocols, &mut count) == 0
+                || protocols.is_null()
+            {
+                return Vec::new();
+            }
+
+            let atoms = std::slice::from_raw_parts(protocols, count.max(0) as usize)
+                .iter()
+                .map(|atom| *atom as Atom)
+                .collect();
+            XFree(protocols as *mut c_void);
+            atoms
+        }
+    }
+
+    fn send_configure_notify(&self, window: WindowId, area: Quad, border_width: u32) {
+        let notify = XConfigureEvent {
+            type_: ConfigureNotify,
+            serial: 0,
+            send_event: true as c_int,
+            display: self.display,
+            event: window as Window,
+            window: window as Window,
+            x: area.x as c_int,
+            y: area.y as c_int,
+            width: area.w as c_int,
+            height: area.h as c_int,
+            border_width: border_width as c_int,
+            above: 0,
+            override_redirect: false as c_int,
+        };
+
+        unsafe {
+            XSendEvent(
+                self.display,
+                window as Window,
+                false as c_int,
+                StructureNotifyMask,
+                &mut XEvent::from(notify),
+            );
+        }
+    }
+
+    fn send_client_message(&self, window: WindowId, message_type: Atom, data: &[c_long]) {
+        let mut message = XClientMessageEvent {
+            type_: ClientMessage,
+            serial: 0,
+            send_event: true as c_int,
+            display: self.display,
+            window: window as Window,
+            message_type: message_type as c_ulong,
+            format: 32,
+            data: ClientMessageData::new(),
+        };
+        for (i, value) in data.iter().take(5).enumerate() {
+            message.data.set_long(i, *value);
+        }
+
+        unsafe {
+            XSendEvent(
+                self.display,
+                window as Window,
+                false as c_int,
+                NoEventMask,
+                &mut XEvent::from(message),
+            );
+        }
+    }
+
+    fn existing_windows(&self) -> Option<Vec<WindowId>> {
+        unsafe {
+            /* Safe as XQueryTree returns count children on success, freed with XFree */
+            let (mut root, mut parent) = (0, 0);
+            let mut children: *mut Window = std::ptr::null_mut();
+            let mut count = 0;
+
+            if XQueryTree(
+                self.display,
+                self.root,
+                &mut root,
+                &mut parent,
+                &mut children,
+                &mut count,
+            ) == 0
+            {
+                return None;
+            }
+            trace!(
+                "Root: {:#?} Parent: {:#?} Number of existing: {:#?}",
+                root,
+                parent,
+                count
+            );
+
+            if children.is_null() {
+                return Some(Vec::new());
+            }
+            let windows = std::slice::from_raw_parts(children, count as usize)
+                .iter()
+                .map(|window| *window as WindowId)
+                .collect();
+            XFree(children as *mut c_void);
+            Some(windows)
+        }
+    }
+
+    fn grab_server(&self) {
+        unsafe {
+            XGrabServer(self.display);
+        }
+    }
+
+    fn ungrab_server(&self) {
+        unsafe {
+            XUngrabServer(self.display);
+        }
+    }
+
+    fn sync(&self) {
+        unsafe {
+            XSync(self.display, false as c_int);
+        }
+    }
+
+    fn pending(&self) -> bool {
+        unsafe { XPending(self.display) > 0 }
+    }
+
+    fn next_event(&self) -> Event {
+        let mut event = unsafe {
+            /* MaybeUninit is safe because XNextEvent will always write _something_ */
+            let mut event = std::mem::MaybeUninit::<XEvent>::zeroed().assume_init();
+            XNextEvent(self.display, &mut event);
+            event
+        };
+        self.decode(&mut event)
+    }
+
+    fn connection(&self) -> c_int {
+        /* Safe as the display is open for the lifetime of the backend */
+        unsafe { XConnectionNumber(self.display) }
+    }
+}
+
+impl Keysyms for XlibBackend {
+    fn keysym(&self, name: &str) -> Option<Keysym> {
+        KeysymNames.keysym(name)
+    }
+}
+
+/// Error handler installed while selecting substructure redirection on the root window, which
+/// fails with BadAccess if another X client (ie. another window manager) already has.
+unsafe extern "C" fn on_startup_error(_display: *mut Display, event: *mut XErrorEvent) -> c_int {
+    /* Safe as Xlib passes a valid error event */
+    let error = XError::from(&*event);
+
+    if error.is_wm_detected() {
+        *WM_DETECTED.lock().unwrap() = true;
+    } else {
+        error!("X error during startup: {}", error);
+    }
+    0 /* This is ignored */
+}
+
+/// Error handler installed once connected. Errors caused by clients exiting mid-request are expected
+/// and only traced; the rest are logged, but never fatal.
+unsafe extern "C" fn on_error(_display: *mut Display, event: *mut XErrorEvent) -> c_int {
+    /* Safe as Xlib passes a valid error event */
+    let error = XError::from(&*event);
+
+    if error.is_benign() {
+        trace!("Ignoring X error: {}", error);
+    } else {
+        error!("X error: {}", error);
+    }
+    0 /* This is ignored */
+}
+
+/// A backend without a display server, for tests. Windows are only book-kept, clients killed and
+/// messages sent are recorded, and the remaining requests are ignored; no events ever arrive.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct MockBackend {
+    /// The geometry of each output, as reported by ```outputs```.
+    pub screens: Vec<Quad>,
+    /// The ```WM_PROTOCOLS``` each client window supports; none, unless listed.
+    pub protocols: RefCell<std::collections::HashMap<WindowId, Vec<Atom>>>,
+    /// Windows whose clients were killed, in order.
+    pub killed: RefCell<Vec<WindowId>>,
+    /// Client messages sent, in order, as (window, message type, data).
+    pub messages: RefCell<Vec<(WindowId, Atom, Vec<c_long>)>>,
+    /// Windows configured, in order, with the value mask of the changes applied.
+    pub configured: RefCell<Vec<(WindowId, c_uint)>>,
+    /// Text properties, by window and property.
+    pub text: RefCell<std::collections::HashMap<(WindowId, Atom), String>>,
+    windows: RefCell<std::collections::HashMap<WindowId, MockWindow>>,
+    next_window: std::cell::Cell<WindowId>,
+    atoms: RefCell<Vec<String>>,
+}
+
+/// The state of a window of a ```MockBackend```.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MockWindow {
+    pub parent: WindowId,
+    pub area: Quad,
+    pub border_width: u32,
+    pub mapped: bool,
+}
+
+#[cfg(test)]
+impl MockBackend {
+    const ROOT: WindowId = 1;
+
+    /// A backend with one output of the given geometry.
+    pub fn new(screen: Quad) -> Self {
+        MockBackend {
+            screens: vec![screen],
+            protocols: RefCell::new(std::collections::HashMap::new()),
+            killed: RefCell::new(Vec::new()),
+            messages: RefCell::new(Vec::new()),
+            configured: RefCell::new(Vec::new()),
+            text: RefCell::new(std::collections::HashMap::new()),
+            windows: RefCell::new(std::collections::HashMap::new()),
+            next_window: std::cell::Cell::new(MockBackend::ROOT + 1),
+            atoms: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns ```None``` if the window was never created, or has been destroyed.
+    pub fn window(&self, window: WindowId) -> Option<MockWindow> {
+        self.windows.borrow().get(&window).copied()
+    }
+
+    fn update(&self, window: WindowId, f: impl FnOnce(&mut MockWindow)) {
+        if let Some(state) = self.windows.borrow_mut().get_mut(&window) {
+            f(state);
+        }
+    }
+}
+
+#[cfg(test)]
+impl Keysyms for MockBackend {
+    fn keysym(&self, name: &str) -> Option<Keysym> {
+        KeysymNames.keysym(name)
+    }
+}
+
+#[cfg(test)]
+impl Backend for MockBackend {
+    fn root(&self) -> WindowId {
+        MockBackend::ROOT
+    }
+
+    fn acquire_selection(&self, _replace: bool) -> Result<ManagerSelection, StartupError> {
+        let window = self.create_window(MockBackend::ROOT, Quad::default(), 0, 0);
+        Ok(ManagerSelection::new(self.intern_atom("WM_S0"), window))
+    }
+
+    fn redirect_root(&self) -> bool {
+        true
+    }
+
+    fn outputs(&self) -> Vec<Quad> {
+        self.screens.clone()
+    }
+
+    fn create_window(
+        &self,
+        parent: WindowId,
+        area: Quad,
+        border_width: u32,
+        _pixel: c_ulong,
+    ) -> WindowId {
+        let window = self.next_window.get();
+        self.next_window.set(window + 1);
+        self.windows.borrow_mut().insert(
+            window,
+            MockWindow {
+                parent,
+                area,
+                border_width,
+                mapped: false,
+            },
+        );
+        window
+    }
+
+    fn destroy_window(&self, window: WindowId) {
+        self.windows.borrow_mut().remove(&window);
+    }
+
+    fn reparent(&self, window: WindowId, parent: WindowId, x: c_int, y: c_int) {
+        self.update(window, |state| {
+            state.parent = parent;
+            state.area.x = x as u32;
+            state.area.y = y as u32;
+        });
+    }
+
+    fn map(&self, window: WindowId) {
+        self.update(window, |state| state.mapped = true);
+    }
+
+    fn unmap(&self, window: WindowId) {
+        self.update(window, |state| state.mapped = false);
+    }
+
+    fn move_resize(&self, window: WindowId, area: Quad) {
+        self.update(window, |state| state.area = area);
+    }
+
+    fn configure(&self, window: WindowId, changes: &WindowChanges, mask: c_uint) {
+        self.configured.borrow_mut().push((window, mask));
+        self.update(window, |state| {
+            if mask & CW_X != 0 {
+                state.area.x = changes.x as u32;
+            }
+            if mask & CW_Y != 0 {
+                state.area.y = changes.y as u32;
+            }
+            if mask & CW_WIDTH != 0 {
+                state.area.w = changes.width as u32;
+            }
+            if mask & CW_HEIGHT != 0 {
+                state.area.h = changes.height as u32;
+            }
+        });
+    }
+
+    fn restack(&self, _windows: &[WindowId]) {}
+
+    fn set_border_width(&self, window: WindowId, width: u32) {
+        self.update(window, |state| state.border_width = width);
+    }
+
+    fn set_border_pixel(&self, _window: WindowId, _pixel: c_ulong) {}
+
+    fn window_attributes(&self, window: WindowId) -> Option<WindowAttributes> {
+        self.window(window).map(|state| WindowAttributes {
+            area: state.area,
+            border_width: state.border_width,
+            override_redirect: false,
+            map_state: if state.mapped {
+                MapState::Viewable
+            } else {
+                MapState::Unmapped
+            },
+        })
+    }
+
+    fn add_to_save_set(&self, _window: WindowId) {}
+
+    fn remove_from_save_set(&self, _window: WindowId) {}
+
+    fn kill_client(&self, window: WindowId) {
+        self.killed.borrow_mut().push(window);
+    }
+
+    fn select_input(&self, _window: WindowId, _mask: c_long) {}
+
+    fn focus(&self, _window: WindowId) {}
+
+    fn grab_button(&self, _window: WindowId, _button: c_uint, _modifiers: c_uint) {}
+
+    fn keycode(&self, keysym: Keysym) -> Option<Keycode> {
+        std::convert::TryFrom::try_from(keysym).ok()
+    }
+
+    fn grab_key(&self, _window: WindowId, _keycode: Keycode, _modifiers: c_uint) {}
+
+    fn ungrab_keys(&self, _window: WindowId) {}
+
+    fn numlock_mask(&self) -> c_uint {
+        MOD2_MASK
+    }
+
+    fn alloc_colour(&self, rgb: c_ulong) -> Option<c_ulong> {
+        Some(rgb)
+    }
+
+    fn free_colours(&self, _pixels: &[c_ulong]) {}
+
+    fn black_pixel(&self) -> c_ulong {
+        0
+    }
+
+    fn intern_atom(&self, name: &str) -> Atom {
+        let mut atoms = self.atoms.borrow_mut();
+        let index = match atoms.iter().position(|atom| atom == name) {
+            Some(index) => index,
+            None => {
+                atoms.push(name.into());
+                atoms.len() - 1
+            }
+        };
+        /* Atoms start at 1, as 0 is None */
+        index as Atom + 1
+    }
+
+    fn set_property(&self, _window: WindowId, _property: Atom, _kind: Atom, _data: &[c_long]) {}
+
+    fn set_text_property(&self, window: WindowId, property: Atom, _kind: Atom, text: &str) {
+        self.text
+            .borrow_mut()
+            .insert((window, property), text.into());
+    }
+
+    fn protocols(&self, window: WindowId) -> Vec<Atom> {
+        self.protocols
+            .borrow()
+            .get(&window)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn send_configure_notify(&self, _window: WindowId, _area: Quad, _border_width: u32) {}
+
+    fn send_client_message(&self, window: WindowId, message_type: Atom, data: &[c_long]) {
+        self.messages
+            .borrow_mut()
+            .push((window, message_type, data.to_vec()));
+    }
+
+    fn existing_windows(&self) -> Option<Vec<WindowId>> {
+        let windows = self.windows.borrow();
+        let mut existing: Vec<WindowId> = windows
+            .iter()
+            .filter(|(_, state)| state.parent == MockBackend::ROOT)
+            .map(|(window, _)| *window)
+            .collect();
+        existing.sort_unstable();
+        Some(existing)
+    }
+
+    fn grab_server(&self) {}
+
+    fn ungrab_server(&self) {}
+
+    fn sync(&self) {}
+
+    fn pending(&self) -> bool {
+        false
+    }
+
+    fn next_event(&self) -> Event {
+        Event::Ignored
+    }
+
+    fn connection(&self) -> c_int {
+        -1
+    }
+}
+
+#[test]
+pub fn neutral_constants_match_xlib() {
+    assert_eq!(
+        [SHIFT_MASK, LOCK_MASK, CONTROL_MASK, MOD1_MASK, MOD4_MASK],
+        [ShiftMask, LockMask, ControlMask, Mod1Mask, Mod4Mask]
+    );
+    assert_eq!(
+        [
+            ENTER_WINDOW_MASK,
+            LEAVE_WINDOW_MASK,
+            SUBSTRUCTURE_NOTIFY_MASK,
+            SUBSTRUCTURE_REDIRECT_MASK,
+            FOCUS_CHANGE_MASK
+        ],
+        [
+            EnterWindowMask,
+            LeaveWindowMask,
+            SubstructureNotifyMask,
+            SubstructureRedirectMask,
+            FocusChangeMask
+        ]
+    );
+    assert_eq!(
+        [CW_X, CW_Y, CW_WIDTH, CW_HEIGHT, CW_SIBLING, CW_STACK_MODE],
+        [CWX, CWY, CWWidth, CWHeight, CWSibling, CWStackMode].map(|mask| mask as c_uint)
+    );
+    assert_eq!((BUTTON1, CURRENT_TIME), (Button1, CurrentTime as c_long));
+}
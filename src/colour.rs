@@ -0,0 +1,293 @@
+use super::backend::Backend;
+use super::config::{Borders, Colour, XColour};
+use libc::*;
+
+/// [X11 colour names](https://en.wikipedia.org/wiki/X11_color_names#Color_name_chart) and their RGB
+/// values, as in the X server's ```rgb.txt```. Names are lowercase without spaces, sorted for binary
+/// search.
+const X11_COLOURS: &[(&str, XColour)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("debianred", 0xd70751),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0xbebebe),
+    ("green", 0x00ff00),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0xbebebe),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrod", 0xeedd82),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslateblue", 0x8470ff),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0xb03060),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("navyblue", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0xa020f0),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("violetred", 0xd02090),
+    ("webgray", 0x808080),
+    ("webgreen", 0x008000),
+    ("webgrey", 0x808080),
+    ("webmaroon", 0x800000),
+    ("webpurple", 0x800080),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("x11gray", 0xbebebe),
+    ("x11green", 0x00ff00),
+    ("x11grey", 0xbebebe),
+    ("x11maroon", 0xb03060),
+    ("x11purple", 0xa020f0),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+/// Resolves a colour as written in ```config.toml``` to a 24-bit RGB value. In order of precedence, a
+/// colour may be:
+/// 1. The name of a user-defined (or built-in) ```[[colour]]```;
+/// 2. A hexadecimal ```#rrggbb``` string; or
+/// 3. An X11 colour name, eg. ```"dark slate gray"```.
+///
+/// Names are case-insensitive, and spaces and underscores are interchangeable.
+pub fn resolve(name: &str, colours: &[Colour]) -> Option<XColour> {
+    let normalise = |s: &str| s.trim().to_lowercase().replace('_', " ");
+    let name = normalise(name);
+
+    if let Some(colour) = colours.iter().find(|c| normalise(&c.name) == name) {
+        return Some(colour.value);
+    }
+
+    if let Some(hex) = name.strip_prefix('#') {
+        /* from_str_radix alone would accept a sign, eg. "#+12345" */
+        return match hex.len() {
+            6 if hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                XColour::from_str_radix(hex, 16).ok()
+            }
+            _ => None,
+        };
+    }
+
+    let compact: String = name.chars().filter(|c| !c.is_whitespace()).collect();
+    X11_COLOURS
+        .binary_search_by(|(n, _)| n.cmp(&compact.as_str()))
+        .ok()
+        .map(|i| X11_COLOURS[i].1)
+}
+
+/// Border colours allocated as pixel values of the default colormap, ready to be given to X.
+/// Pixels must be allocated rather than assumed to be RGB values, as the two only coincide on
+/// TrueColor visuals.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    allocated: Vec<c_ulong>,
+    /// Border of unfocused clients.
+    pub normal: c_ulong,
+    /// Border of the focused client.
+    pub focus: c_ulong,
+}
+
+impl Palette {
+    pub fn alloc(backend: &dyn Backend, borders: &Borders) -> Self {
+        let mut palette = Palette {
+            allocated: Vec::new(),
+            normal: 0,
+            focus: 0,
+        };
+
+        palette.normal = palette.pixel(backend, borders.colour);
+        palette.focus = if borders.no_focus_colour {
+            palette.normal
+        } else {
+            palette.pixel(backend, borders.focus_colour)
+        };
+        palette
+    }
+
+    /// Returns the pixels allocated by this palette to the colormap, eg. before a reload.
+    pub fn free(&mut self, backend: &dyn Backend) {
+        backend.free_colours(&self.allocated);
+        self.allocated.clear();
+    }
+
+    /// Allocates the closest colour the colormap supports, falling back to black if the colormap is
+    /// full.
+    fn pixel(&mut self, backend: &dyn Backend, rgb: XColour) -> c_ulong {
+        match backend.alloc_colour(rgb) {
+            Some(pixel) => {
+                self.allocated.push(pixel);
+                pixel
+            }
+            None => {
+                warn!("Could not allocate colour #{:06x}, using black", rgb);
+                backend.black_pixel()
+            }
+        }
+    }
+}
+
+#[test]
+pub fn resolve_colours() {
+    let user = vec![Colour {
+        name: "Burnt Umber".into(),
+        value: 0x8a3324,
+    }];
+
+    assert_eq!(resolve("burnt_umber", &user), Some(0x8a3324));
+    assert_eq!(resolve("#CCCCFF", &user), Some(0xccccff));
+    assert_eq!(resolve("Dark Slate Gray", &user), Some(0x2f4f4f));
+    assert_eq!(resolve("dark_slate_gray", &user), Some(0x2f4f4f));
+    assert_eq!(resolve("Rebecca Purple", &user), Some(0x663399));
+    assert_eq!(resolve("web_maroon", &user), Some(0x800000));
+    assert_eq!(resolve("x11_maroon", &user), Some(0xb03060));
+    for name in &[
+        "aqua",
+        "crimson",
+        "fuchsia",
+        "indigo",
+        "lime",
+        "olive",
+        "silver",
+        "teal",
+        "webgray",
+        "webgreen",
+        "webpurple",
+    ] {
+        assert!(resolve(name, &user).is_some(), "{}", name);
+    }
+    assert_eq!(resolve("periwinkle", &user), None);
+    assert_eq!(resolve("#ccf", &user), None);
+    assert_eq!(resolve("#+12345", &user), None);
+    assert_eq!(resolve("#-12345", &user), None);
+    assert_eq!(resolve("#12345g", &user), None);
+
+    assert!(X11_COLOURS.windows(2).all(|w| w[0].0 < w[1].0));
+}
@@ -1,14 +1,104 @@
-#![allow(unused_imports)]
+#![allow(unused_imports, dead_code)]
+use super::backend::Keysyms;
+use super::colour;
+use super::keys::{KeyCombo, KeysymNames};
+use super::layout::Layouts;
+use super::operation::Operation;
 use libc::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::path::PathBuf;
-use x11::xlib::*;
+use std::path::{Path, PathBuf};
 
-type XColour = c_ulong;
-const PATH: &str = "/home/blinklad/dev/rust/rdwm/src/config.toml";
+pub type XColour = c_ulong;
+
+/// Sub-directory and file name of the configuration file, relative to any XDG configuration directory.
+const CONFIG_FILE: &str = "rdwm/config.toml";
+
+/// System configuration directories searched when ```$XDG_CONFIG_DIRS``` is unset or empty, as per the
+/// XDG base directory specification.
+const DEFAULT_CONFIG_DIRS: &str = "/etc/xdg";
+
+/// Largest accepted ```inner_gap``` and ```outer_gap```, in pixels.
+pub const MAX_GAP: u8 = 100;
+
+/// Accepted range of ```master_ratio```, so that neither the master nor stack area disappears.
+const MASTER_RATIO: (f64, f64) = (0.05, 0.95);
+
+/// Largest number of tags, one per bit of a tag mask.
+pub const MAX_TAGS: usize = 32;
+
+/// Comments emitted above each section and setting by ```Config::default_toml```, keyed by TOML path.
+const DOCUMENTATION: &[(&str, &str)] = &[
+    ("windows", "Size and behaviour of tiled client windows."),
+    (
+        "windows.inner_gap",
+        "Gap between adjacent windows, in pixels.",
+    ),
+    (
+        "windows.outer_gap",
+        "Gap between windows and the screen edge, in pixels.",
+    ),
+    (
+        "windows.smart_gaps",
+        "Omit gaps when a workspace holds a single window.",
+    ),
+    (
+        "windows.master_ratio",
+        "Fraction of the screen width given to the master area, between 0.05 and 0.95.",
+    ),
+    ("windows.nmaster", "Number of windows in the master area."),
+    (
+        "windows.kill_timeout",
+        "Milliseconds that \"kill focus\" waits for a window to close itself, if it supports\n\
+         WM_DELETE_WINDOW, before forcibly disconnecting it; 0 never disconnects it. A window that\n\
+         answers _NET_WM_PING in time (eg. while asking to save changes) is never disconnected.",
+    ),
+    (
+        "windows.layouts",
+        "Layouts each workspace cycles through, starting with the first.\n\
+         One of tile, monocle, grid, spiral, dwindle or centeredmaster.",
+    ),
+    ("borders", "Size and appearance of window borders."),
+    (
+        "borders.colour",
+        "Border colour of unfocused windows; a [[colour]] name, X11 colour name or \"#rrggbb\".",
+    ),
+    ("borders.size", "Border width, in pixels."),
+    (
+        "borders.focus_colour",
+        "Border colour of the focused window; a [[colour]] name, X11 colour name or \"#rrggbb\".",
+    ),
+    (
+        "borders.no_focus_colour",
+        "Use the unfocused colour for the focused window too.",
+    ),
+    (
+        "borders.smart",
+        "Hide the border of a window that is alone on screen, eg. in monocle.",
+    ),
+    ("workspaces", "Workspaces or tags that clients are grouped into."),
+    (
+        "workspaces.model",
+        "\"workspaces\": each client is on one workspace, and one workspace is shown at a time.\n\
+         \"tags\": each client carries any number of tags, and any number of tags is shown at a\n\
+         time, as in dwm. Workspace operations then view and tag a single tag.",
+    ),
+    (
+        "workspaces.names",
+        "One name per workspace or tag (at most 32 tags); operations such as \"workspace 2\" count\n\
+         from 1.",
+    ),
+    (
+        "binding",
+        "Global keybindings: modifiers (alt, shift, control, super, mod1-5) plus one key name.\n\
+         The operation names a [[command]], or a built-in operation such as \"kill focus\".",
+    ),
+    ("command", "Named shell commands, run with /bin/sh -c."),
+    ("colour", "Named colours, as hexadecimal RGB values."),
+];
 
 // TODO Documentation for configuration options should follow this convention:
 // https://github.com/rust-lang/rustfmt/blob/master/Configurations.md
@@ -17,13 +107,14 @@ const PATH: &str = "/home/blinklad/dev/rust/rdwm/src/config.toml";
 /// Holds runtime state of changes, if applicable.
 /// Operations and data are mostly opaque to Rdwm proper, which is mainly just to _respond_ to events
 /// by messaging appropriate handlers and handle any window-related book-keeping.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Config {
     windows: Option<ArrangementSettings>,
     borders: Option<BorderSettings>,
-    #[serde(alias = "binding")]
+    workspaces: Option<WorkspaceSettings>,
+    #[serde(rename = "binding", alias = "bindings")]
     bindings: Option<Vec<KeySettings>>,
-    #[serde(alias = "command")]
+    #[serde(rename = "command", alias = "commands")]
     commands: Option<Vec<CommandSettings>>,
     colour: Option<Vec<ColourSettings>>,
 }
@@ -32,31 +123,58 @@ pub struct Config {
 /// [arrangement] section of configuration file.
 /// Arrangement settings are any settings that modify the size, behaviour or structure of client
 /// windows. For example, size of inner gaps (default 0) or whether to ignore gaps for a single client
-/// window on a workspace.
+/// window on a workspace (default false).
 struct ArrangementSettings {
     inner_gap: Option<u8>,
     outer_gap: Option<u8>,
     smart_gaps: Option<bool>,
+    master_ratio: Option<f64>,
+    nmaster: Option<usize>,
+    kill_timeout: Option<u64>,
+    layouts: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 /// [border] section of configuration file.
 /// Border settings are any settings that modify the size, appearance or behaviour of client window
-/// borders. For example, the size of window borders (default 0) or colours for window urgency or
+/// borders. For example, the size of window borders (default 3) or colours for window urgency or
 /// non-focussed windows.
+/// Colours may refer to a ```[[colour]]``` by name, an X11 colour name or a ```"#rrggbb"``` string.
 struct BorderSettings {
     colour: Option<String>,
     size: Option<usize>,
     focus_colour: Option<String>,
     no_focus_colour: Option<bool>,
+    smart: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// [workspaces] section of configuration file.
+/// Workspace settings describe the set of workspaces that clients are grouped into, one of which is
+/// shown at a time. Workspaces are addressed by number (counting from 1) in operations, eg.
+/// ```workspace 2```, and their names are purely descriptive.
+/// With ```model = "tags"```, the names are instead those of dwm-style tags.
+struct WorkspaceSettings {
+    model: Option<Model>,
+    names: Option<Vec<String>>,
+}
+
+/// How clients are grouped, see ```[workspaces]```.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Model {
+    /// Exclusive workspaces, one shown at a time.
+    Workspaces,
+    /// A tag bitmask per client, with the union of several tags shown at a time.
+    Tags,
 }
 
 /// [binding] section of configuration file.
 /// Binding settings are any settings that modify the behaviour of keystrokes globally.
-/// Binding key _names_ are [pre-specified](TODO), and there are two built-in levels of precedence
+/// Binding key _names_ are described by ```KeyCombo```, and there are two built-in levels of precedence
 /// for the _values_:
 /// 1. Refers to an optional, user-supplied ```Operation``` by a named key; or
-/// 2. Refers to a built-in operation (for example, close the focused window)
+/// 2. Refers to a built-in ```Operation``` (for example, close the focused window)
 ///
 /// For example, in ```config.toml```:
 /// ```
@@ -80,9 +198,10 @@ struct KeySettings {
 /// [commands] section of configuration file.
 /// Command settings are named values for to-be-executed commands, purely as a convenience for
 /// keybinding and per-window rule settings.
-/// [several](TODO) 'built-in' commands exist, such as ```kill focus```, ```kill all```, ```exec```.
+/// [several](crate::operation::Operation) 'built-in' commands exist, such as ```kill focus```,
+/// ```kill all```, ```exec```.
 /// User-supplied commands are (for the time being) assumed to run as narrowly POSIX compliant
-/// shell scripts.
+/// shell scripts, ie. with ```/bin/sh -c```, detached from Rdwm.
 /// For example, in ```config.toml```:
 /// ```
 /// [[command]]
@@ -122,11 +241,397 @@ struct ColourSettings {
     value: Option<XColour>,
 }
 
+/// Fully resolved configuration consumed by Rdwm proper.
+/// Built from the compiled-in defaults, with any user-supplied ```Config``` overlaid field by field,
+/// such that a partial ```config.toml``` still produces a working window manager.
+#[derive(Debug, Clone)]
+pub struct EffectiveConfig {
+    pub arrangement: Arrangement,
+    pub borders: Borders,
+    pub workspaces: Workspaces,
+    pub bindings: Vec<Binding>,
+    pub commands: Vec<Command>,
+    pub colours: Vec<Colour>,
+}
+
+/// Resolved ```[windows]``` section.
+#[derive(Debug, Clone)]
+pub struct Arrangement {
+    pub inner_gap: u8,
+    pub outer_gap: u8,
+    pub smart_gaps: bool,
+    pub master_ratio: f64,
+    pub nmaster: usize,
+    pub kill_timeout: u64,
+    pub layouts: Vec<String>,
+}
+
+/// Resolved ```[borders]``` section. Colour names are resolved against the ```[[colour]]``` table.
+#[derive(Debug, Clone)]
+pub struct Borders {
+    pub colour: XColour,
+    pub size: usize,
+    pub focus_colour: XColour,
+    pub no_focus_colour: bool,
+    pub smart: bool,
+}
+
+/// Resolved ```[workspaces]``` section.
+#[derive(Debug, Clone)]
+pub struct Workspaces {
+    pub model: Model,
+    pub names: Vec<String>,
+}
+
+/// Resolved ```[[binding]]``` entry.
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub name: Option<String>,
+    pub keys: Vec<String>,
+    pub operation: String,
+}
+
+/// Resolved ```[[command]]``` entry.
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: String,
+    pub action: String,
+}
+
+/// Resolved ```[[colour]]``` entry.
+#[derive(Debug, Clone)]
+pub struct Colour {
+    pub name: String,
+    pub value: XColour,
+}
+
+/// Names of the built-in colours used by the default ```[borders]``` section.
+const DEFAULT_COLOUR: &str = "rdwm purple";
+const DEFAULT_FOCUS_COLOUR: &str = "rdwm yellow";
+
+impl Default for EffectiveConfig {
+    /// Compiled-in 'sensible' defaults.
+    fn default() -> Self {
+        let colours = vec![
+            Colour {
+                name: DEFAULT_COLOUR.into(),
+                value: 0x5f316d,
+            },
+            Colour {
+                name: DEFAULT_FOCUS_COLOUR.into(),
+                value: 0xEEE8AA,
+            },
+        ];
+
+        EffectiveConfig {
+            arrangement: Arrangement {
+                inner_gap: 0,
+                outer_gap: 0,
+                smart_gaps: false,
+                master_ratio: 0.55,
+                nmaster: 1,
+                kill_timeout: 5000,
+                layouts: vec!["tile".into(), "monocle".into()],
+            },
+            borders: Borders {
+                colour: colours[0].value,
+                size: 3,
+                focus_colour: colours[1].value,
+                no_focus_colour: false,
+                smart: false,
+            },
+            workspaces: Workspaces {
+                model: Model::Workspaces,
+                names: (1..=9).map(|n| n.to_string()).collect(),
+            },
+            bindings: vec![
+                Binding {
+                    name: None,
+                    keys: vec!["alt".into(), "enter".into()],
+                    operation: "term".into(),
+                },
+                Binding {
+                    name: None,
+                    keys: vec!["alt".into(), "shift".into(), "q".into()],
+                    operation: "kill focus".into(),
+                },
+                Binding {
+                    name: None,
+                    keys: vec!["alt".into(), "tab".into()],
+                    operation: "workspace previous".into(),
+                },
+            ]
+            .into_iter()
+            .chain((1..=9).flat_map(|n| {
+                [
+                    Binding {
+                        name: None,
+                        keys: vec!["alt".into(), n.to_string()],
+                        operation: format!("workspace {}", n),
+                    },
+                    Binding {
+                        name: None,
+                        keys: vec!["alt".into(), "shift".into(), n.to_string()],
+                        operation: format!("workspace move {}", n),
+                    },
+                ]
+            }))
+            .collect(),
+            commands: vec![Command {
+                name: "term".into(),
+                action: "exec xterm".into(),
+            }],
+            colours,
+        }
+    }
+}
+
+impl EffectiveConfig {
+    /// Resolves a user configuration against the compiled-in defaults.
+    /// Scalar settings replace their default outright. Colours and commands replace a default of
+    /// the same name, and bindings replace a default bound to the same keys; anything else is
+    /// appended. Incomplete table entries are ignored.
+    pub fn resolve(user: Config) -> Self {
+        let mut config = EffectiveConfig::default();
+
+        for colour in user.colour.unwrap_or_default() {
+            match colour {
+                ColourSettings {
+                    name: Some(name),
+                    value: Some(value),
+                } => {
+                    config.colours.retain(|c| !same_name(&c.name, &name));
+                    config.colours.push(Colour { name, value });
+                }
+                _ => warn!("Ignoring incomplete colour: {:?}", colour),
+            }
+        }
+
+        for command in user.commands.unwrap_or_default() {
+            match command {
+                CommandSettings {
+                    name: Some(name),
+                    action: Some(action),
+                } => {
+                    config.commands.retain(|c| c.name != name);
+                    config.commands.push(Command { name, action });
+                }
+                _ => warn!("Ignoring incomplete command: {:?}", command),
+            }
+        }
+
+        for binding in user.bindings.unwrap_or_default() {
+            match binding {
+                KeySettings {
+                    name,
+                    keys: Some(keys),
+                    operation: Some(operation),
+                } => {
+                    config.bindings.retain(|b| !same_keys(&b.keys, &keys));
+                    config.bindings.push(Binding {
+                        name,
+                        keys,
+                        operation,
+                    });
+                }
+                _ => warn!("Ignoring incomplete binding: {:?}", binding),
+            }
+        }
+
+        if let Some(arrangement) = user.windows {
+            let a = &mut config.arrangement;
+            a.inner_gap = arrangement.inner_gap.unwrap_or(a.inner_gap);
+            a.outer_gap = arrangement.outer_gap.unwrap_or(a.outer_gap);
+            a.smart_gaps = arrangement.smart_gaps.unwrap_or(a.smart_gaps);
+            a.master_ratio = arrangement.master_ratio.unwrap_or(a.master_ratio);
+            a.nmaster = arrangement.nmaster.unwrap_or(a.nmaster);
+            a.kill_timeout = arrangement.kill_timeout.unwrap_or(a.kill_timeout);
+            if let Some(layouts) = arrangement.layouts {
+                a.layouts = layouts;
+            }
+        }
+
+        if let Some(borders) = user.borders {
+            if let Some(name) = borders.colour {
+                config.borders.colour = config.colour(&name).unwrap_or(config.borders.colour);
+            }
+            if let Some(name) = borders.focus_colour {
+                config.borders.focus_colour =
+                    config.colour(&name).unwrap_or(config.borders.focus_colour);
+            }
+
+            let b = &mut config.borders;
+            b.size = borders.size.unwrap_or(b.size);
+            b.no_focus_colour = borders.no_focus_colour.unwrap_or(b.no_focus_colour);
+            b.smart = borders.smart.unwrap_or(b.smart);
+        }
+
+        if let Some(workspaces) = user.workspaces {
+            let w = &mut config.workspaces;
+            w.model = workspaces.model.unwrap_or(w.model);
+            if let Some(names) = workspaces.names {
+                w.names = names;
+            }
+        }
+
+        config
+    }
+
+    /// Resolves a colour by name, logging names that do not exist.
+    fn colour(&self, name: &str) -> Option<XColour> {
+        let colour = colour::resolve(name, &self.colours);
+        if colour.is_none() {
+            warn!("Unknown colour {:?}, using default", name);
+        }
+        colour
+    }
+}
+
+impl From<&EffectiveConfig> for Config {
+    /// The inverse of ```EffectiveConfig::resolve```, where border colours are named by the first
+    /// ```[[colour]]``` of the same value.
+    fn from(config: &EffectiveConfig) -> Self {
+        let colour_name = |value: XColour| {
+            config
+                .colours
+                .iter()
+                .find(|c| c.value == value)
+                .map(|c| c.name.clone())
+        };
+
+        Config {
+            windows: Some(ArrangementSettings {
+                inner_gap: Some(config.arrangement.inner_gap),
+                outer_gap: Some(config.arrangement.outer_gap),
+                smart_gaps: Some(config.arrangement.smart_gaps),
+                master_ratio: Some(config.arrangement.master_ratio),
+                nmaster: Some(config.arrangement.nmaster),
+                kill_timeout: Some(config.arrangement.kill_timeout),
+                layouts: Some(config.arrangement.layouts.clone()),
+            }),
+            borders: Some(BorderSettings {
+                colour: colour_name(config.borders.colour),
+                size: Some(config.borders.size),
+                focus_colour: colour_name(config.borders.focus_colour),
+                no_focus_colour: Some(config.borders.no_focus_colour),
+                smart: Some(config.borders.smart),
+            }),
+            workspaces: Some(WorkspaceSettings {
+                model: Some(config.workspaces.model),
+                names: Some(config.workspaces.names.clone()),
+            }),
+            bindings: Some(
+                config
+                    .bindings
+                    .iter()
+                    .map(|b| KeySettings {
+                        name: b.name.clone(),
+                        keys: Some(b.keys.clone()),
+                        operation: Some(b.operation.clone()),
+                    })
+                    .collect(),
+            ),
+            commands: Some(
+                config
+                    .commands
+                    .iter()
+                    .map(|c| CommandSettings {
+                        name: Some(c.name.clone()),
+                        action: Some(c.action.clone()),
+                    })
+                    .collect(),
+            ),
+            colour: Some(
+                config
+                    .colours
+                    .iter()
+                    .map(|c| ColourSettings {
+                        name: Some(c.name.clone()),
+                        value: Some(c.value),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Whether a binding's operation refers to a command by name, or to a built-in operation (which may
+/// take arguments, eg. ```exec xterm```).
+fn is_operation(operation: &str, commands: &[&str]) -> bool {
+    commands.contains(&operation) || operation.parse::<Operation>().is_ok()
+}
+
+/// Colour names are matched case-insensitively, treating underscores as spaces, so that
+/// ```"burnt_umber"``` refers to ```name = "Burnt Umber"```.
+fn same_name(a: &str, b: &str) -> bool {
+    let normalise = |s: &str| s.trim().to_lowercase().replace('_', " ");
+    normalise(a) == normalise(b)
+}
+
+/// Key combinations are unordered and case-insensitive, eg. ```["shift", "alt", "Q"]``` is the same
+/// binding as ```["alt", "shift", "q"]```.
+fn same_keys(a: &[String], b: &[String]) -> bool {
+    let normalise = |keys: &[String]| {
+        let mut keys: Vec<String> = keys.iter().map(|k| k.trim().to_lowercase()).collect();
+        keys.sort();
+        keys
+    };
+    normalise(a) == normalise(b)
+}
+
+/// A setting that is well-formed TOML but meaningless to Rdwm, located by its path within the file,
+/// eg. ```binding[2].operation```. Array indices count from 0 in file order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub location: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(location: String, message: String) -> Self {
+        Diagnostic { location, message }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Errors raised while reading a configuration file from disk.
+/// Neither is fatal to Rdwm; the caller is expected to fall back to default values.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file exists but could not be read.
+    Io(PathBuf, std::io::Error),
+    /// The file is not well-formed TOML, or does not match the expected structure.
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "could not read {}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => {
+                write!(f, "could not parse {}: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
 impl Config {
-    /// Produces a Rdwm configuration from either:
-    /// 1. XDG base directory;
-    /// 2. /etc/share/ defaults;
-    /// 3. Application default values ('sensible' defaults)
+    /// Produces a Rdwm configuration from the first file found in:
+    /// 1. An explicit path, ie. ```rdwm --config <path>```;
+    /// 2. ```$XDG_CONFIG_HOME/rdwm/config.toml``` (```~/.config/rdwm/config.toml``` if unset);
+    /// 3. ```$XDG_CONFIG_DIRS/rdwm/config.toml``` (```/etc/xdg/rdwm/config.toml``` if unset);
+    /// 4. Application default values ('sensible' defaults)
+    ///
+    /// A missing file falls through to the next location. A file that exists but cannot be read or
+    /// parsed is logged and replaced by application defaults, rather than silently picking up a
+    /// lower-precedence file.
     ///
     /// Once a base configuration is established, it may be the case that a well-formed
     /// config.toml file is invalid - eg. a colour or binding is named erroneously.
@@ -137,20 +642,502 @@ impl Config {
     /// Lastly, the result of a command (eg. exit status or IPC information) is not specified at
     /// this stage. It may be logged, but is likely ignored.
     ///
-    pub fn get_config() -> Self {
-        let config = PathBuf::from(PATH);
-        let mut file = File::open(config).unwrap();
-        let mut contents = String::new();
-        let _bytes = file.read_to_string(&mut contents);
-        let settings: Config = toml::from_str(&contents).unwrap();
+    pub fn get_config<K: Keysyms + ?Sized>(
+        path: Option<&Path>,
+        keysyms: &K,
+        layouts: &Layouts,
+    ) -> Self {
+        let settings = match Config::locate(path) {
+            Some(file) => match Config::from_path(&file) {
+                Ok(settings) => {
+                    info!("Loaded configuration from {}", file.display());
+                    let (settings, diagnostics) = settings.validate(keysyms, layouts);
+                    for diagnostic in diagnostics {
+                        warn!("{}: ignoring {}", file.display(), diagnostic);
+                    }
+                    settings
+                }
+                Err(err) => {
+                    error!("{}; using default configuration", err);
+                    Config::default()
+                }
+            },
+            None => {
+                info!("No configuration file found; using default configuration");
+                Config::default()
+            }
+        };
 
         debug!("{:#?}", settings);
         settings
     }
+
+    /// Reads and parses a single configuration file.
+    pub fn from_path(path: &Path) -> Result<Self, ConfigError> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+
+        toml::from_str(&contents).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+    }
+
+    /// Checks every setting that refers to another setting, or to something outside of the file, eg.
+    /// colour and command names, key names (as looked up in ```keysyms```), layout names (as
+    /// registered in ```layouts```) and gap sizes.
+    /// Returns the configuration with each offending entry dropped (so that it falls back to its
+    /// default), alongside a diagnostic for each.
+    pub fn validate<K: Keysyms + ?Sized>(
+        mut self,
+        keysyms: &K,
+        layouts: &Layouts,
+    ) -> (Self, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let mut report = |location: String, message: String| {
+            diagnostics.push(Diagnostic::new(location, message))
+        };
+
+        /* Colours and commands first, as borders and bindings refer to them */
+        if let Some(colours) = self.colour.as_mut() {
+            let mut index = 0..;
+            colours.retain(|colour| {
+                let location = format!("colour[{}]", index.next().unwrap());
+                match colour {
+                    ColourSettings { name: None, .. } => report(location, "missing name".into()),
+                    ColourSettings { value: None, .. } => report(location, "missing value".into()),
+                    _ => return true,
+                }
+                false
+            });
+        }
+
+        if let Some(commands) = self.commands.as_mut() {
+            let mut index = 0..;
+            commands.retain(|command| {
+                let location = format!("command[{}]", index.next().unwrap());
+                match command {
+                    CommandSettings { name: None, .. } => report(location, "missing name".into()),
+                    CommandSettings { action: None, .. } => {
+                        report(location, "missing action".into())
+                    }
+                    _ => return true,
+                }
+                false
+            });
+        }
+
+        let defaults = EffectiveConfig::default();
+        let colours: Vec<Colour> = defaults
+            .colours
+            .iter()
+            .cloned()
+            .chain(self.colour.iter().flatten().filter_map(|c| {
+                Some(Colour {
+                    name: c.name.clone()?,
+                    value: c.value?,
+                })
+            }))
+            .collect();
+        let command_names: Vec<&str> = defaults
+            .commands
+            .iter()
+            .map(|c| c.name.as_str())
+            .chain(
+                self.commands
+                    .iter()
+                    .flatten()
+                    .filter_map(|c| c.name.as_deref()),
+            )
+            .collect();
+
+        if let Some(bindings) = self.bindings.as_mut() {
+            let mut index = 0..;
+            let mut bound: Vec<KeyCombo> = Vec::new();
+            bindings.retain(|binding| {
+                let location = format!("binding[{}]", index.next().unwrap());
+                let (keys, operation) = match binding {
+                    KeySettings {
+                        keys: Some(keys),
+                        operation: Some(operation),
+                        ..
+                    } => (keys, operation),
+                    KeySettings { keys: None, .. } => {
+                        report(location, "missing keys".into());
+                        return false;
+                    }
+                    KeySettings { .. } => {
+                        report(location, "missing operation".into());
+                        return false;
+                    }
+                };
+
+                let combo = match KeyCombo::parse(keys, keysyms) {
+                    Ok(combo) => combo,
+                    Err(err) => {
+                        report(format!("{}.keys", location), err.to_string());
+                        return false;
+                    }
+                };
+                if bound.contains(&combo) {
+                    report(
+                        format!("{}.keys", location),
+                        format!("duplicate binding for {:?}", keys),
+                    );
+                    return false;
+                }
+                if !is_operation(operation, &command_names) {
+                    report(
+                        format!("{}.operation", location),
+                        format!(
+                            "{:?} is neither a [[command]] nor a built-in operation",
+                            operation
+                        ),
+                    );
+                    return false;
+                }
+
+                bound.push(combo);
+                true
+            });
+        }
+
+        if let Some(borders) = self.borders.as_mut() {
+            for (field, colour) in [
+                ("colour", &mut borders.colour),
+                ("focus_colour", &mut borders.focus_colour),
+            ] {
+                if let Some(name) = colour.as_deref() {
+                    if colour::resolve(name, &colours).is_none() {
+                        report(
+                            format!("borders.{}", field),
+                            format!("unknown colour {:?}", name),
+                        );
+                        *colour = None;
+                    }
+                }
+            }
+        }
+
+        if let Some(windows) = self.windows.as_mut() {
+            for (field, gap) in [
+                ("inner_gap", &mut windows.inner_gap),
+                ("outer_gap", &mut windows.outer_gap),
+            ] {
+                if let Some(size) = *gap {
+                    if size > MAX_GAP {
+                        report(
+                            format!("windows.{}", field),
+                            format!("gap of {} exceeds maximum of {}", size, MAX_GAP),
+                        );
+                        *gap = None;
+                    }
+                }
+            }
+
+            if let Some(ratio) = windows.master_ratio {
+                let (min, max) = MASTER_RATIO;
+                if !(min..=max).contains(&ratio) {
+                    report(
+                        "windows.master_ratio".into(),
+                        format!("{} is not between {} and {}", ratio, min, max),
+                    );
+                    windows.master_ratio = None;
+                }
+            }
+
+            if let Some(names) = windows.layouts.as_mut() {
+                let mut index = 0..;
+                names.retain(|name| {
+                    let location = format!("windows.layouts[{}]", index.next().unwrap());
+                    let known = layouts.get(name).is_some();
+                    if !known {
+                        report(location, format!("unknown layout {:?}", name));
+                    }
+                    known
+                });
+
+                if names.is_empty() {
+                    report("windows.layouts".into(), "no layouts given".into());
+                    windows.layouts = None;
+                }
+            }
+        }
+
+        if let Some(workspaces) = self.workspaces.as_mut() {
+            if workspaces.names.as_ref().is_some_and(Vec::is_empty) {
+                report("workspaces.names".into(), "no workspaces given".into());
+                workspaces.names = None;
+            }
+
+            let names = workspaces
+                .names
+                .as_ref()
+                .map_or(defaults.workspaces.names.len(), Vec::len);
+            if workspaces.model == Some(Model::Tags) && names > MAX_TAGS {
+                report(
+                    "workspaces.names".into(),
+                    format!("{} tags exceeds maximum of {}", names, MAX_TAGS),
+                );
+                workspaces.names = None;
+            }
+        }
+
+        (self, diagnostics)
+    }
+
+    /// Renders the compiled-in defaults as a ```config.toml```, with each section and setting
+    /// preceded by a comment describing it.
+    pub fn default_toml() -> String {
+        let config = Config::from(&EffectiveConfig::default());
+        let table = match toml::Value::try_from(&config) {
+            Ok(toml::Value::Table(table)) => table,
+            _ => unreachable!("Config always serializes to a table"),
+        };
+
+        let comment = |out: &mut String, key: &str| {
+            if let Some((_, doc)) = DOCUMENTATION.iter().find(|(k, _)| *k == key) {
+                for line in doc.lines() {
+                    out.push_str(&format!("# {}\n", line));
+                }
+            }
+        };
+
+        let mut out = String::from("# Default rdwm configuration.\n");
+        for section in &[
+            "windows",
+            "borders",
+            "workspaces",
+            "binding",
+            "command",
+            "colour",
+        ] {
+            let entries = match table.get(*section) {
+                Some(toml::Value::Table(table)) => vec![(false, table)],
+                Some(toml::Value::Array(array)) => array
+                    .iter()
+                    .filter_map(|v| v.as_table().map(|t| (true, t)))
+                    .collect(),
+                _ => continue,
+            };
+
+            out.push('\n');
+            comment(&mut out, section);
+            for (n, (is_array, entry)) in entries.into_iter().enumerate() {
+                if is_array {
+                    if n > 0 {
+                        out.push('\n');
+                    }
+                    out.push_str(&format!("[[{}]]\n", section));
+                } else {
+                    out.push_str(&format!("[{}]\n", section));
+                }
+                for (key, value) in entry {
+                    if !is_array {
+                        comment(&mut out, &format!("{}.{}", section, key));
+                    }
+                    match value {
+                        /* Colours read better in hexadecimal */
+                        toml::Value::Integer(rgb) if *section == "colour" => {
+                            out.push_str(&format!("{} = 0x{:06x}\n", key, rgb))
+                        }
+                        _ => out.push_str(&format!("{} = {}\n", key, value)),
+                    }
+                }
+            }
+        }
+
+        out.trim_end().to_string() + "\n"
+    }
+
+    /// Returns the highest precedence configuration file that exists, if any.
+    /// An explicit path that does not exist is reported, then the XDG locations are searched.
+    pub fn locate(path: Option<&Path>) -> Option<PathBuf> {
+        if let Some(path) = path {
+            if path.is_file() {
+                return Some(path.to_path_buf());
+            }
+            warn!("Configuration file {} does not exist", path.display());
+        }
+
+        Config::search_paths().into_iter().find(|p| p.is_file())
+    }
+
+    /// The configuration file to watch for changes: the file in use, or failing that the explicit
+    /// path, or the first XDG location, so that a configuration file created later is picked up.
+    pub fn watch_path(path: Option<&Path>) -> Option<PathBuf> {
+        Config::locate(path)
+            .or_else(|| path.map(Path::to_path_buf))
+            .or_else(|| Config::search_paths().into_iter().next())
+    }
+
+    /// Candidate configuration files in order of precedence, as per the XDG base directory
+    /// specification. Relative directories are ignored, as the specification requires.
+    fn search_paths() -> Vec<PathBuf> {
+        let non_empty = |var: &str| std::env::var_os(var).filter(|v| !v.is_empty());
+
+        let home = non_empty("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+        let dirs = non_empty("XDG_CONFIG_DIRS").unwrap_or_else(|| DEFAULT_CONFIG_DIRS.into());
+
+        home.into_iter()
+            .chain(std::env::split_paths(&dirs))
+            .filter(|dir| dir.is_absolute())
+            .map(|dir| dir.join(CONFIG_FILE))
+            .collect()
+    }
 }
 
 #[test]
 pub fn get_config() {
-    let config = Config::get_config();
+    let dir = std::env::temp_dir().join(format!("rdwm-config-{}", std::process::id()));
+    let path = dir.join(CONFIG_FILE);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(
+        &path,
+        "[windows]\ninner_gap = 6\nouter_gap = 200\nlayouts = [\"monocle\"]\n\n\
+         [borders]\nfocus_colour = \"#ff0000\"\n",
+    )
+    .unwrap();
+
+    /* Valid settings are taken from the file, and invalid ones (outer_gap) fall back to defaults */
+    let config = Config::get_config(Some(&path), &KeysymNames, &Layouts::builtin());
+    let config = EffectiveConfig::resolve(config);
+    let defaults = EffectiveConfig::default();
+    assert_eq!(config.arrangement.inner_gap, 6);
+    assert_eq!(config.arrangement.outer_gap, defaults.arrangement.outer_gap);
+    assert_eq!(config.arrangement.layouts, ["monocle"]);
+    assert_eq!(config.borders.focus_colour, 0xff0000);
+    assert_eq!(config.borders.colour, defaults.borders.colour);
+    assert_eq!(config.bindings.len(), defaults.bindings.len());
+
+    /* A file that does not parse is ignored as a whole */
+    std::fs::write(&path, "[windows\n").unwrap();
+    let config = Config::get_config(Some(&path), &KeysymNames, &Layouts::builtin());
+    let config = EffectiveConfig::resolve(config);
+    assert_eq!(config.arrangement.inner_gap, defaults.arrangement.inner_gap);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+pub fn partial_config() {
+    let user: Config = toml::from_str("[windows]\ninner_gap = 8\n").unwrap();
+    let config = EffectiveConfig::resolve(user);
+    let defaults = EffectiveConfig::default();
+
+    assert_eq!(config.arrangement.inner_gap, 8);
+    assert_eq!(config.arrangement.outer_gap, defaults.arrangement.outer_gap);
+    assert_eq!(config.borders.size, defaults.borders.size);
+    assert_eq!(config.bindings.len(), defaults.bindings.len());
+}
+
+#[test]
+pub fn validate_config() {
+    let user: Config = toml::from_str(
+        r#"
+        [windows]
+        inner_gap = 200
+
+        [borders]
+        colour = "nope"
+        focus_colour = "rdwm_yellow"
+
+        [[binding]]
+        keys = ["alt", "enter"]
+        operation = "term"
+
+        [[binding]]
+        keys = ["enter", "alt"]
+        operation = "kill focus"
+
+        [[binding]]
+        keys = ["alt", "bogus"]
+        operation = "term"
+
+        [[binding]]
+        keys = ["alt", "x"]
+        operation = "missing"
+        "#,
+    )
+    .unwrap();
+
+    let (config, diagnostics) = user.validate(&KeysymNames, &Layouts::builtin());
+    let locations: Vec<&str> = diagnostics.iter().map(|d| d.location.as_str()).collect();
+    assert_eq!(
+        locations,
+        [
+            "binding[1].keys",
+            "binding[2].keys",
+            "binding[3].operation",
+            "borders.colour",
+            "windows.inner_gap"
+        ]
+    );
+
+    assert_eq!(config.bindings.as_ref().unwrap().len(), 1);
+    assert!(config.borders.as_ref().unwrap().colour.is_none());
+    assert!(config.borders.as_ref().unwrap().focus_colour.is_some());
+}
+
+#[test]
+pub fn default_toml() {
+    let printed = Config::default_toml();
+    println!("{}", printed);
+
+    let (config, diagnostics) = toml::from_str::<Config>(&printed)
+        .unwrap()
+        .validate(&KeysymNames, &Layouts::builtin());
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let config = EffectiveConfig::resolve(config);
+    let defaults = EffectiveConfig::default();
+    assert_eq!(config.borders.focus_colour, defaults.borders.focus_colour);
+    assert_eq!(config.bindings.len(), defaults.bindings.len());
+}
+
+#[test]
+pub fn example_config() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/config.toml");
+    let (config, diagnostics) = Config::from_path(&path)
+        .unwrap()
+        .validate(&KeysymNames, &Layouts::builtin());
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let config = EffectiveConfig::resolve(config);
     println!("{:#?}", config);
+
+    assert_eq!(config.borders.colour, 0xCCCCFF);
+    assert_eq!(config.borders.focus_colour, 0x8a3324);
+}
+
+#[test]
+pub fn registered_layouts() {
+    use super::layout::{Layout, Params, Quad};
+
+    #[derive(Debug)]
+    struct Columns;
+    impl Layout for Columns {
+        fn name(&self) -> &'static str {
+            "columns"
+        }
+        fn arrange(&self, area: Quad, n: usize, _params: &Params) -> Vec<Quad> {
+            area.columns(n as u32)
+        }
+    }
+
+    let user = || toml::from_str::<Config>("[windows]\nlayouts = [\"columns\"]\n").unwrap();
+    let mut layouts = Layouts::builtin();
+    let (_, diagnostics) = user().validate(&KeysymNames, &layouts);
+    assert_eq!(diagnostics.len(), 2);
+
+    layouts.register(std::rc::Rc::new(Columns));
+    let (config, diagnostics) = user().validate(&KeysymNames, &layouts);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    let config = EffectiveConfig::resolve(config);
+    assert_eq!(
+        layouts.resolve(&config.arrangement.layouts)[0].name(),
+        "columns"
+    );
 }
@@ -0,0 +1,195 @@
+use super::backend::*;
+use libc::*;
+use std::fmt;
+
+/// Modifier names accepted in a ```[[binding]]```, mapped onto X modifier masks.
+const MODIFIERS: &[(&str, c_uint)] = &[
+    ("shift", SHIFT_MASK),
+    ("lock", LOCK_MASK),
+    ("control", CONTROL_MASK),
+    ("ctrl", CONTROL_MASK),
+    ("alt", MOD1_MASK),
+    ("mod1", MOD1_MASK),
+    ("mod2", MOD2_MASK),
+    ("mod3", MOD3_MASK),
+    ("super", MOD4_MASK),
+    ("win", MOD4_MASK),
+    ("mod4", MOD4_MASK),
+    ("mod5", MOD5_MASK),
+];
+
+include!(concat!(env!("OUT_DIR"), "/keysyms.rs"));
+
+/// Friendlier names for keys whose X keysym names are not obvious.
+const ALIASES: &[(&str, &str)] = &[
+    ("enter", "Return"),
+    ("esc", "Escape"),
+    ("del", "Delete"),
+    ("backspace", "BackSpace"),
+    ("pageup", "Prior"),
+    ("pagedown", "Next"),
+    ("printscreen", "Print"),
+];
+
+/// A single key combined with any number of modifiers, eg. ```["alt", "shift", "q"]```.
+///
+/// Keys are defined using a simplified version of the XBindKeys conventions: modifiers are named
+/// (case-insensitively) as in ```MODIFIERS```, and exactly one key is named by its X keysym name
+/// (```"q"```, ```"Return"```, ```"F1"```, ```"XF86AudioMute"```) or a friendlier alias such as
+/// ```"enter"```. Keysym names are matched case-insensitively where X does not distinguish them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub modifiers: c_uint,
+    pub keysym: Keysym,
+}
+
+/// Reasons a list of key names does not describe a ```KeyCombo```.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyError {
+    /// Neither a modifier nor a known keysym name.
+    Unknown(String),
+    /// Only modifiers were given.
+    NoKey,
+    /// More than one non-modifier key was given.
+    MultipleKeys(String, String),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyError::Unknown(name) => write!(f, "unknown key name {:?}", name),
+            KeyError::NoKey => write!(f, "no key given besides modifiers"),
+            KeyError::MultipleKeys(a, b) => {
+                write!(f, "more than one key given ({:?} and {:?})", a, b)
+            }
+        }
+    }
+}
+
+impl KeyCombo {
+    /// Parses a binding's ```keys``` list, looking key names up in the given keysyms, eg. a
+    /// ```Backend```. Does not require a connection to the X server.
+    pub fn parse<K: Keysyms + ?Sized>(keys: &[String], keysyms: &K) -> Result<Self, KeyError> {
+        let mut modifiers = 0;
+        let mut key: Option<(&str, Keysym)> = None;
+
+        for name in keys {
+            let name = name.trim();
+
+            if let Some((_, mask)) = MODIFIERS.iter().find(|(m, _)| m.eq_ignore_ascii_case(name)) {
+                modifiers |= mask;
+                continue;
+            }
+
+            let keysym = keysym(keysyms, name).ok_or_else(|| KeyError::Unknown(name.into()))?;
+            if let Some((previous, _)) = key {
+                return Err(KeyError::MultipleKeys(previous.into(), name.into()));
+            }
+            key = Some((name, keysym));
+        }
+
+        key.map(|(_, keysym)| KeyCombo { modifiers, keysym })
+            .ok_or(KeyError::NoKey)
+    }
+}
+
+/// Modifiers that may distinguish one binding from another. Lock modifiers are excluded.
+const BINDABLE: c_uint =
+    SHIFT_MASK | CONTROL_MASK | MOD1_MASK | MOD2_MASK | MOD3_MASK | MOD4_MASK | MOD5_MASK;
+
+/// Strips lock modifiers (Caps Lock, Num Lock) and pointer buttons from an event's modifier state,
+/// so that it may be compared to a ```KeyCombo```.
+pub fn clean_mask(state: c_uint, numlock: c_uint) -> c_uint {
+    state & !(numlock | LOCK_MASK) & BINDABLE
+}
+
+impl KeyCombo {
+    /// Grabs the combination on the given window, including every variant with Caps Lock and Num
+    /// Lock active so that bindings work regardless of lock state.
+    /// Returns false if the keysym is not mapped to any key on the current keyboard.
+    pub fn grab(&self, backend: &dyn Backend, window: WindowId, numlock: c_uint) -> bool {
+        let keycode = match backend.keycode(self.keysym) {
+            Some(keycode) => keycode,
+            None => return false,
+        };
+
+        for locks in &[0, LOCK_MASK, numlock, numlock | LOCK_MASK] {
+            backend.grab_key(window, keycode, self.modifiers | locks);
+        }
+        true
+    }
+
+    /// Whether a key press, of the given key and modifier state, was caused by this combination.
+    pub fn matches(
+        &self,
+        backend: &dyn Backend,
+        keycode: Keycode,
+        state: c_uint,
+        numlock: c_uint,
+    ) -> bool {
+        backend.keycode(self.keysym) == Some(keycode)
+            && clean_mask(self.modifiers, numlock) == clean_mask(state, numlock)
+    }
+}
+
+/// Keysym names as in ```keysymdef.h``` and ```XF86keysym.h```, looked up in a table generated from
+/// those headers at build time, so that neither Xlib nor a display is needed, eg. by
+/// ```--check-config```.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeysymNames;
+
+impl Keysyms for KeysymNames {
+    fn keysym(&self, name: &str) -> Option<Keysym> {
+        KEYSYMS
+            .binary_search_by(|(known, _)| (*known).cmp(name))
+            .ok()
+            .map(|index| KEYSYMS[index].1)
+    }
+}
+
+/// Looks up a keysym by name, trying aliases, then the name as given, then common capitalisations
+/// (eg. ```"return"``` as ```"Return"```, ```"f1"``` as ```"F1"```).
+fn keysym<K: Keysyms + ?Sized>(keysyms: &K, name: &str) -> Option<Keysym> {
+    let lookup = |name: &str| keysyms.keysym(name);
+    if let Some((_, alias)) = ALIASES.iter().find(|(a, _)| a.eq_ignore_ascii_case(name)) {
+        return lookup(alias);
+    }
+
+    let mut capitalised = name.to_lowercase();
+    if let Some(first) = capitalised.get_mut(0..1) {
+        first.make_ascii_uppercase();
+    }
+
+    lookup(name)
+        .or_else(|| lookup(&capitalised))
+        .or_else(|| lookup(&name.to_uppercase()))
+}
+
+#[test]
+pub fn parse_keys() {
+    let keys = |k: &[&str]| {
+        let keys: Vec<String> = k.iter().map(|s| s.to_string()).collect();
+        KeyCombo::parse(&keys, &KeysymNames)
+    };
+
+    let combo = keys(&["alt", "Shift", "enter"]).unwrap();
+    assert_eq!(combo.modifiers, MOD1_MASK | SHIFT_MASK);
+    assert_eq!(combo.keysym, 0xff0d /* XK_Return */);
+    assert_eq!(
+        keys(&["super", "f1"]).unwrap().keysym,
+        0xffbe /* XK_F1 */
+    );
+
+    assert_eq!(keys(&["q"]).unwrap().keysym, 'q' as Keysym);
+    assert_eq!(
+        keys(&["XF86AudioMute"]).unwrap().keysym,
+        0x1008ff12 /* XF86XK_AudioMute */
+    );
+
+    assert_eq!(keys(&["alt"]), Err(KeyError::NoKey));
+    assert_eq!(
+        keys(&["alt", "nope"]),
+        Err(KeyError::Unknown("nope".into()))
+    );
+    assert!(keys(&["a", "b"]).is_err());
+}
@@ -0,0 +1,628 @@
+//! Arrangements of tiled clients over a screen area.
+//! Layouts are pure functions from an area and a number of clients to one cell per client (in
+//! client order), such that they may be tested without an X server. Cells are outer geometries; it
+//! is up to the caller to fit window borders within them.
+#![allow(dead_code)]
+
+use std::fmt::Debug;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A 4-tuple of integers used to plot a point on a screen as a co-ordinate vector.
+pub struct Quad {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Quad {
+    pub fn default() -> Self {
+        Quad {
+            x: 0,
+            y: 0,
+            w: 0,
+            h: 0,
+        }
+    }
+
+    pub fn from_size(h: u32, w: u32) -> Self {
+        Quad { x: 0, y: 0, w, h }
+    }
+
+    pub fn from_coords(x: u32, y: u32) -> Self {
+        Quad { x, y, h: 0, w: 0 }
+    }
+
+    /// Splits the quad into ```n``` rows of (as near as possible) equal height, top to bottom.
+    /// Any remainder is distributed one pixel at a time, so that the rows cover the quad exactly.
+    pub fn rows(&self, n: u32) -> Vec<Quad> {
+        let mut y = self.y;
+        (0..n)
+            .map(|i| {
+                let h = self.h / n + u32::from(i < self.h % n);
+                let row = Quad { y, h, ..*self };
+                y += h;
+                row
+            })
+            .collect()
+    }
+
+    /// Splits the quad into ```n``` columns of (as near as possible) equal width, left to right.
+    pub fn columns(&self, n: u32) -> Vec<Quad> {
+        self.transpose()
+            .rows(n)
+            .iter()
+            .map(Quad::transpose)
+            .collect()
+    }
+
+    /// Splits the quad vertically, into top and bottom parts, giving the top part ```ratio``` of the
+    /// height.
+    pub fn split_vertical(&self, ratio: f64) -> (Quad, Quad) {
+        let (top, bottom) = self.transpose().split_horizontal(ratio);
+        (top.transpose(), bottom.transpose())
+    }
+
+    /// Swaps the x and y axes.
+    fn transpose(&self) -> Quad {
+        Quad {
+            x: self.y,
+            y: self.x,
+            w: self.h,
+            h: self.w,
+        }
+    }
+
+    /// Splits the quad horizontally, into left and right parts, giving the left part ```ratio``` of
+    /// the width.
+    pub fn split_horizontal(&self, ratio: f64) -> (Quad, Quad) {
+        let w = (self.w as f64 * ratio).round() as u32;
+        (
+            Quad { w, ..*self },
+            Quad {
+                x: self.x + w,
+                w: self.w - w,
+                ..*self
+            },
+        )
+    }
+}
+
+/// Per-workspace parameters that layouts may take into account, adjustable at runtime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Params {
+    /// Number of clients in the master area.
+    pub nmaster: usize,
+    /// Fraction of the width given to the master area.
+    pub master_ratio: f64,
+}
+
+/// Space left empty around tiled clients, in pixels. Applied to every layout by ```Gaps::apply```.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Gaps {
+    /// Gap between adjacent cells.
+    pub inner: u32,
+    /// Gap between cells and the edge of the area.
+    pub outer: u32,
+}
+
+impl Gaps {
+    /// Arranges ```n``` clients with a layout, within the area less the outer gap, then shrinks each
+    /// cell away from its neighbours such that adjacent cells are ```inner``` pixels apart.
+    /// Cell edges on the border of the area are left alone, so stacked layouts are unaffected by the
+    /// inner gap.
+    pub fn apply(&self, layout: &dyn Layout, area: Quad, n: usize, params: &Params) -> Vec<Quad> {
+        let outer = self.outer.min(area.w / 2).min(area.h / 2);
+        let inside = Quad {
+            x: area.x + outer,
+            y: area.y + outer,
+            w: area.w - 2 * outer,
+            h: area.h - 2 * outer,
+        };
+
+        /* Each side of a shared edge gives up half the gap, the leading side any odd pixel */
+        let (before, after) = (self.inner - self.inner / 2, self.inner / 2);
+        layout
+            .arrange(inside, n, params)
+            .into_iter()
+            .map(|cell| {
+                let left = if cell.x > inside.x { before } else { 0 };
+                let top = if cell.y > inside.y { before } else { 0 };
+                let right = if cell.x + cell.w < inside.x + inside.w {
+                    after
+                } else {
+                    0
+                };
+                let bottom = if cell.y + cell.h < inside.y + inside.h {
+                    after
+                } else {
+                    0
+                };
+
+                Quad {
+                    x: cell.x + left,
+                    y: cell.y + top,
+                    w: cell.w.saturating_sub(left + right),
+                    h: cell.h.saturating_sub(top + bottom),
+                }
+            })
+            .collect()
+    }
+}
+
+/// An arrangement of tiled clients, referred to in ```config.toml``` by name.
+/// Implement this, and register it with the ```Layouts``` that ```main``` passes to ```Rdwm::init```
+/// (or add it to ```Layouts::builtin```), to add a layout to Rdwm.
+pub trait Layout: Debug {
+    /// Name used to refer to the layout in ```config.toml```, eg. ```layouts = ["tile"]```.
+    fn name(&self) -> &'static str;
+
+    /// Returns one cell per tiled client, in client order, within the given area.
+    fn arrange(&self, area: Quad, n: usize, params: &Params) -> Vec<Quad>;
+
+    /// Whether cells overlap, such that only the focused client is visible. Workspaces report how
+    /// many clients are hidden in stacked layouts.
+    fn stacked(&self) -> bool {
+        false
+    }
+
+    /// Short description of the arrangement for a bar, given the number of tiled clients and the
+    /// position of the focused client amongst them.
+    fn symbol(&self, _n: usize, _selected: usize) -> String {
+        format!("[{}]", self.name())
+    }
+}
+
+/// Registry of layouts by name.
+#[derive(Debug, Clone)]
+pub struct Layouts {
+    layouts: Vec<Rc<dyn Layout>>,
+}
+
+impl Layouts {
+    /// Layouts built into Rdwm.
+    pub fn builtin() -> Self {
+        let mut layouts = Layouts {
+            layouts: Vec::new(),
+        };
+        layouts.register(Rc::new(Tile));
+        layouts.register(Rc::new(Monocle));
+        layouts.register(Rc::new(Grid));
+        layouts.register(Rc::new(Fibonacci { spiral: true }));
+        layouts.register(Rc::new(Fibonacci { spiral: false }));
+        layouts.register(Rc::new(CenteredMaster));
+        layouts
+    }
+
+    /// Adds a layout, replacing any existing layout of the same name.
+    pub fn register(&mut self, layout: Rc<dyn Layout>) {
+        self.layouts.retain(|l| l.name() != layout.name());
+        self.layouts.push(layout);
+    }
+
+    /// Looks up a layout by (case-insensitive) name.
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Layout>> {
+        self.layouts
+            .iter()
+            .find(|l| l.name().eq_ignore_ascii_case(name.trim()))
+            .cloned()
+    }
+
+    /// Resolves a list of layout names, skipping (and logging) unknown names. Falls back to the
+    /// first registered layout if none are known, so that the result is never empty.
+    pub fn resolve(&self, names: &[String]) -> Vec<Rc<dyn Layout>> {
+        let mut layouts: Vec<Rc<dyn Layout>> = names
+            .iter()
+            .filter_map(|name| {
+                let layout = self.get(name);
+                if layout.is_none() {
+                    warn!("Unknown layout {:?}", name);
+                }
+                layout
+            })
+            .collect();
+
+        if layouts.is_empty() {
+            layouts.extend(self.layouts.first().cloned());
+        }
+        layouts
+    }
+}
+
+/// See ```tile```.
+#[derive(Debug)]
+pub struct Tile;
+
+impl Layout for Tile {
+    fn name(&self) -> &'static str {
+        "tile"
+    }
+
+    fn arrange(&self, area: Quad, n: usize, params: &Params) -> Vec<Quad> {
+        tile(area, n, params.nmaster, params.master_ratio)
+    }
+}
+
+/// See ```monocle```.
+#[derive(Debug)]
+pub struct Monocle;
+
+impl Layout for Monocle {
+    fn name(&self) -> &'static str {
+        "monocle"
+    }
+
+    fn arrange(&self, area: Quad, n: usize, _params: &Params) -> Vec<Quad> {
+        monocle(area, n)
+    }
+
+    fn stacked(&self) -> bool {
+        true
+    }
+
+    /// Position of the focused client, eg. ```[3/7]```.
+    fn symbol(&self, n: usize, selected: usize) -> String {
+        format!("[{}/{}]", (selected + 1).min(n), n)
+    }
+}
+
+/// Every client takes the whole area, stacked atop one another.
+pub fn monocle(area: Quad, n: usize) -> Vec<Quad> {
+    vec![area; n]
+}
+
+/// dwm's classic tile layout: the first ```nmaster``` clients are stacked in a master area on the
+/// left, taking ```master_ratio``` of the width, and the rest are stacked on the right.
+/// Either area takes the whole width if the other is empty.
+pub fn tile(area: Quad, n: usize, nmaster: usize, master_ratio: f64) -> Vec<Quad> {
+    let nmaster = nmaster.min(n);
+    let nstack = n - nmaster;
+
+    let (master, stack) = match (nmaster, nstack) {
+        (0, _) => (Quad { w: 0, ..area }, area),
+        (_, 0) => (area, Quad { w: 0, ..area }),
+        _ => area.split_horizontal(master_ratio),
+    };
+
+    let mut cells = master.rows(nmaster as u32);
+    cells.extend(stack.rows(nstack as u32));
+    cells
+}
+
+/// See ```grid```.
+#[derive(Debug)]
+pub struct Grid;
+
+impl Layout for Grid {
+    fn name(&self) -> &'static str {
+        "grid"
+    }
+
+    fn arrange(&self, area: Quad, n: usize, _params: &Params) -> Vec<Quad> {
+        grid(area, n)
+    }
+}
+
+/// Clients in columns of (as near as possible) equal rows, with as many columns as rows.
+/// When the clients do not fill the grid, the rightmost columns take an extra client each, such
+/// that there are no gaps.
+pub fn grid(area: Quad, n: usize) -> Vec<Quad> {
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let columns = (n as f64).sqrt().ceil() as usize;
+    let rows = n / columns;
+    let extra = n % columns;
+
+    area.columns(columns as u32)
+        .iter()
+        .enumerate()
+        .flat_map(|(i, column)| column.rows((rows + usize::from(i >= columns - extra)) as u32))
+        .collect()
+}
+
+/// See ```fibonacci```.
+#[derive(Debug)]
+pub struct Fibonacci {
+    /// Whether to spiral inwards (```spiral```), or to dwindle towards the bottom right
+    /// (```dwindle```).
+    pub spiral: bool,
+}
+
+impl Layout for Fibonacci {
+    fn name(&self) -> &'static str {
+        if self.spiral {
+            "spiral"
+        } else {
+            "dwindle"
+        }
+    }
+
+    fn arrange(&self, area: Quad, n: usize, _params: &Params) -> Vec<Quad> {
+        fibonacci(area, n, self.spiral)
+    }
+}
+
+/// Smallest dimension, in pixels, that ```fibonacci``` will halve a cell down to.
+const MIN_FIBONACCI: u32 = 64;
+
+/// Each client takes half of the area left by the previous client, alternating between vertical
+/// and horizontal splits; the last client takes whatever remains.
+/// When ```spiral``` is set, every other pair of clients takes the right and bottom halves instead,
+/// such that clients spiral in towards the centre.
+/// Once halving would leave a cell smaller than ```MIN_FIBONACCI```, the remaining clients share the
+/// rest as a ```grid```, so that any number of clients get usable cells.
+pub fn fibonacci(area: Quad, n: usize, spiral: bool) -> Vec<Quad> {
+    let mut cells = Vec::with_capacity(n);
+    let mut rest = area;
+
+    for i in 0..n {
+        let left = n - i;
+        if left == 1 {
+            cells.push(rest);
+            break;
+        }
+
+        let vertical = i % 2 == 0;
+        let halved = if vertical { rest.w / 2 } else { rest.h / 2 };
+        if halved < MIN_FIBONACCI {
+            cells.extend(grid(rest, left));
+            break;
+        }
+
+        let (first, second) = if vertical {
+            rest.split_horizontal(0.5)
+        } else {
+            rest.split_vertical(0.5)
+        };
+        /* Spiralling clients take the second half every other pair, ie. right then bottom */
+        let (cell, remainder) = if spiral && i % 4 >= 2 {
+            (second, first)
+        } else {
+            (first, second)
+        };
+
+        cells.push(cell);
+        rest = remainder;
+    }
+
+    cells
+}
+
+/// See ```centered_master```.
+#[derive(Debug)]
+pub struct CenteredMaster;
+
+impl Layout for CenteredMaster {
+    fn name(&self) -> &'static str {
+        "centeredmaster"
+    }
+
+    fn arrange(&self, area: Quad, n: usize, params: &Params) -> Vec<Quad> {
+        centered_master(area, n, params.nmaster, params.master_ratio)
+    }
+}
+
+/// For ultrawide screens: the master area takes ```master_ratio``` of the width in the centre of the
+/// screen, with the remaining clients alternating between stacks to its right and left.
+/// With a single stacked client, this is the same as ```tile```.
+pub fn centered_master(area: Quad, n: usize, nmaster: usize, master_ratio: f64) -> Vec<Quad> {
+    let nmaster = nmaster.min(n);
+    let nstack = n - nmaster;
+    if nstack <= 1 {
+        return tile(area, n, nmaster, master_ratio);
+    }
+
+    let master_w = if nmaster == 0 {
+        0
+    } else {
+        (area.w as f64 * master_ratio).round() as u32
+    };
+    let left_w = (area.w - master_w) / 2;
+
+    let left = Quad { w: left_w, ..area };
+    let master = Quad {
+        x: area.x + left_w,
+        w: master_w,
+        ..area
+    };
+    let right = Quad {
+        x: master.x + master_w,
+        w: area.w - master_w - left_w,
+        ..area
+    };
+
+    /* Stacked clients alternate right, left, right... */
+    let nright = nstack.div_ceil(2);
+    let mut rights = right.rows(nright as u32).into_iter();
+    let mut lefts = left.rows((nstack - nright) as u32).into_iter();
+
+    let mut cells = master.rows(nmaster as u32);
+    for i in 0..nstack {
+        let cell = if i % 2 == 0 {
+            rights.next()
+        } else {
+            lefts.next()
+        };
+        cells.extend(cell);
+    }
+    cells
+}
+
+/// Whether the cells tile the area exactly: each lies within the area, no two overlap and together
+/// they cover its whole surface.
+#[cfg(test)]
+pub fn covers(area: Quad, cells: &[Quad]) -> bool {
+    let within = |c: &Quad| {
+        c.x >= area.x
+            && c.y >= area.y
+            && c.x + c.w <= area.x + area.w
+            && c.y + c.h <= area.y + area.h
+    };
+    let overlap = |a: &Quad, b: &Quad| {
+        a.x < b.x + b.w && b.x < a.x + a.w && a.y < b.y + b.h && b.y < a.y + a.h
+    };
+    let surface: u64 = cells.iter().map(|c| c.w as u64 * c.h as u64).sum();
+
+    cells.iter().all(within)
+        && cells
+            .iter()
+            .enumerate()
+            .all(|(i, a)| cells[i + 1..].iter().all(|b| !overlap(a, b)))
+        && surface == area.w as u64 * area.h as u64
+}
+
+#[test]
+pub fn tile_layout() {
+    let area = Quad {
+        x: 10,
+        y: 20,
+        w: 1001,
+        h: 767,
+    };
+
+    assert!(tile(area, 0, 1, 0.55).is_empty());
+    assert_eq!(tile(area, 1, 1, 0.55), vec![area]);
+
+    let cells = tile(area, 2, 1, 0.5);
+    assert_eq!(cells[0].x, area.x);
+    assert_eq!(cells[0].h, area.h);
+    assert_eq!(cells[1].x, cells[0].x + cells[0].w);
+
+    for n in 0..12 {
+        for nmaster in 0..4 {
+            let cells = tile(area, n, nmaster, 0.55);
+            assert_eq!(cells.len(), n);
+            assert!(n == 0 || covers(area, &cells), "{} {}", n, nmaster);
+        }
+    }
+}
+
+#[test]
+pub fn layout_registry() {
+    let layouts = Layouts::builtin();
+    assert_eq!(layouts.get("Tile").map(|l| l.name()), Some("tile"));
+    assert!(layouts.get("nope").is_none());
+
+    let resolved = layouts.resolve(&["nope".into(), "monocle".into()]);
+    assert_eq!(resolved.len(), 1);
+    assert_eq!(resolved[0].name(), "monocle");
+    assert_eq!(resolved[0].symbol(7, 2), "[3/7]");
+    assert_eq!(layouts.resolve(&[])[0].name(), "tile");
+}
+
+#[test]
+pub fn grid_layout() {
+    let area = Quad::from_size(1080, 1920);
+
+    assert!(grid(area, 0).is_empty());
+    assert_eq!(grid(area, 1), vec![area]);
+    assert_eq!(grid(area, 2), area.columns(2));
+
+    /* 5 clients: 3 columns of 1, 2 and 2 rows */
+    let cells = grid(area, 5);
+    assert_eq!(cells[0].h, area.h);
+    assert_eq!(cells[1].x, cells[2].x);
+
+    for n in [3, 7, 9, 10, 64, 101] {
+        let cells = grid(area, n);
+        assert_eq!(cells.len(), n);
+        assert!(covers(area, &cells), "{}", n);
+        assert!(cells.iter().all(|c| c.w > 0 && c.h > 0), "{}", n);
+    }
+}
+
+#[test]
+pub fn fibonacci_layouts() {
+    let area = Quad {
+        x: 1920,
+        y: 0,
+        w: 1920,
+        h: 1080,
+    };
+
+    for spiral in [true, false] {
+        assert!(fibonacci(area, 0, spiral).is_empty());
+        assert_eq!(fibonacci(area, 1, spiral), vec![area]);
+        assert_eq!(fibonacci(area, 2, spiral), area.columns(2));
+
+        for n in [3, 5, 7, 50, 200] {
+            let cells = fibonacci(area, n, spiral);
+            assert_eq!(cells.len(), n);
+            assert!(covers(area, &cells), "{} {}", n, spiral);
+            assert!(cells.iter().all(|c| c.w > 0 && c.h > 0), "{} {}", n, spiral);
+        }
+    }
+
+    /* The third client of a spiral takes the right, not left, half of what remains */
+    let spiral = fibonacci(area, 4, true);
+    let dwindle = fibonacci(area, 4, false);
+    assert!(spiral[2].x > spiral[3].x);
+    assert!(dwindle[2].x < dwindle[3].x);
+}
+
+#[test]
+pub fn centered_master_layout() {
+    let area = Quad::from_size(1440, 3440);
+
+    assert!(centered_master(area, 0, 1, 0.5).is_empty());
+    assert_eq!(centered_master(area, 1, 1, 0.5), vec![area]);
+    assert_eq!(centered_master(area, 2, 1, 0.5), tile(area, 2, 1, 0.5));
+
+    /* Master in the centre, then right, then left */
+    let cells = centered_master(area, 3, 1, 0.5);
+    assert!(cells[2].x < cells[0].x && cells[0].x < cells[1].x);
+
+    for n in [5, 7, 40] {
+        for nmaster in 0..3 {
+            let cells = centered_master(area, n, nmaster, 0.5);
+            assert_eq!(cells.len(), n);
+            assert!(covers(area, &cells), "{} {}", n, nmaster);
+            assert!(
+                cells.iter().all(|c| c.w > 0 && c.h > 0),
+                "{} {}",
+                n,
+                nmaster
+            );
+        }
+    }
+}
+
+#[test]
+pub fn gaps() {
+    let area = Quad::from_size(1080, 1920);
+    let params = Params {
+        nmaster: 1,
+        master_ratio: 0.5,
+    };
+    let gaps = Gaps {
+        inner: 11,
+        outer: 5,
+    };
+
+    assert_eq!(
+        gaps.apply(&Tile, area, 1, &params),
+        vec![Quad {
+            x: 5,
+            y: 5,
+            w: 1910,
+            h: 1070
+        }]
+    );
+    assert_eq!(
+        Gaps::default().apply(&Tile, area, 3, &params),
+        tile(area, 3, 1, 0.5)
+    );
+
+    /* Adjacent cells are exactly the inner gap apart */
+    let cells = gaps.apply(&Tile, area, 3, &params);
+    assert_eq!(cells[1].x - (cells[0].x + cells[0].w), 11);
+    assert_eq!(cells[2].y - (cells[1].y + cells[1].h), 11);
+    assert_eq!(cells[2].y + cells[2].h, 1075);
+
+    /* Stacked layouts only get the outer gap */
+    let cells = gaps.apply(&Monocle, area, 3, &params);
+    assert!(cells.iter().all(|c| c.w == 1910 && c.h == 1070));
+}
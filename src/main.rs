@@ -5,11 +5,86 @@ extern crate lazy_static;
 #[macro_use]
 extern crate bitflags;
 
+mod backend;
+mod colour;
 mod config;
+mod keys;
+mod layout;
+mod operation;
+mod output;
+mod process;
 mod rdwm;
+mod selection;
+mod signal;
+mod watch;
+mod xerror;
 
+use backend::XlibBackend;
+use config::Config;
 use env_logger::WriteStyle::Auto;
+use keys::KeysymNames;
+use layout::Layouts;
 use rdwm::Rdwm;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::rc::Rc;
+
+/// Printed along with any command line error.
+const USAGE: &str = "\
+usage: rdwm [--config <path>] [--replace]
+       rdwm --check-config [path]
+       rdwm --print-default-config";
+
+/// What Rdwm has been asked to do. Only ```Run``` connects to the X server.
+#[derive(Debug, Default, PartialEq)]
+enum Mode {
+    /// Manage windows on the display named by ```$DISPLAY```.
+    #[default]
+    Run,
+    /// Parse and validate a configuration file, then exit.
+    CheckConfig,
+    /// Print the default configuration file, then exit.
+    PrintDefaultConfig,
+}
+
+/// Command line options accepted by Rdwm.
+#[derive(Debug, Default)]
+struct Options {
+    /// Overrides the XDG configuration file lookup.
+    config: Option<PathBuf>,
+    /// Replaces a running window manager, rather than exiting.
+    replace: bool,
+    mode: Mode,
+}
+
+impl Options {
+    /// Parses options from the process arguments, skipping the program name.
+    fn parse<I: Iterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.peekable();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-c" | "--config" => {
+                    let path = args.next().ok_or(format!("{} expects a path", arg))?;
+                    options.config = Some(PathBuf::from(path));
+                }
+                "--check-config" => {
+                    options.mode = Mode::CheckConfig;
+                    /* The path is optional, and may be given by --config instead */
+                    if let Some(path) = args.next_if(|a| !a.starts_with('-')) {
+                        options.config = Some(PathBuf::from(path));
+                    }
+                }
+                "--print-default-config" => options.mode = Mode::PrintDefaultConfig,
+                "--replace" => options.replace = true,
+                _ => return Err(format!("unrecognised argument: {}", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
 
 /// Starts logging for Rdwm and initialises the main event loop.
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -19,12 +94,78 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .init();
     info!("Starting logger OK");
 
-    let mut rdwm = Rdwm::init()
-        .ok_or("could not connect to display server")
-        .unwrap();
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("rdwm: {}\n{}", err, USAGE);
+            exit(2);
+        }
+    };
+    /* Layouts of one's own are registered here, so that both --check-config and Rdwm know them */
+    let layouts = Layouts::builtin();
+    match options.mode {
+        Mode::Run => (),
+        Mode::CheckConfig => exit(check_config(options.config.as_deref(), &layouts)),
+        Mode::PrintDefaultConfig => {
+            print!("{}", Config::default_toml());
+            return Ok(());
+        }
+    }
+
+    let init = XlibBackend::open().and_then(|backend| {
+        let config = options.config.as_deref();
+        Rdwm::init(Rc::new(backend), layouts, config, options.replace)
+    });
+    let mut rdwm = match init {
+        Ok(rdwm) => rdwm,
+        Err(err) => {
+            eprintln!("rdwm: {}", err);
+            exit(1);
+        }
+    };
     info!("Starting display server OK");
-    rdwm.run();
+
+    if let Err(err) = rdwm.run() {
+        drop(rdwm);
+        eprintln!("rdwm: {}", err);
+        exit(1);
+    }
 
     info!("Finish OK");
     Ok(())
 }
+
+/// Parses and validates a configuration file without connecting to the X server, reporting every
+/// problem found. Returns the process exit status: non-zero if the file has any problems.
+fn check_config(path: Option<&Path>, layouts: &Layouts) -> i32 {
+    let file = match path {
+        Some(path) => path.to_path_buf(),
+        None => match Config::locate(None) {
+            Some(file) => file,
+            None => {
+                println!("No configuration file found; rdwm would use its defaults");
+                return 0;
+            }
+        },
+    };
+
+    let diagnostics = match Config::from_path(&file) {
+        Ok(config) => config.validate(&KeysymNames, layouts).1,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+
+    for diagnostic in &diagnostics {
+        eprintln!("{}: {}", file.display(), diagnostic);
+    }
+
+    if diagnostics.is_empty() {
+        println!("{}: OK", file.display());
+        0
+    } else {
+        eprintln!("{}: {} problem(s) found", file.display(), diagnostics.len());
+        1
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Built-in operations, addressable by name from ```[[binding]]``` entries (and any other input
+/// path, eg. IPC). Every operation is performed by ```Rdwm::execute```.
+///
+/// Names are case-insensitive and whitespace separated, eg. ```"kill focus"``` or
+/// ```"workspace move 2"```. Only ```exec``` takes free-form text, which is passed to the shell as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// ```kill focus```: ask the focused client to close, killing it if it does not.
+    KillFocus,
+    /// ```kill all```: ask every visible client on the current workspace to close.
+    KillAll,
+    /// ```exec <command>```: run a shell command.
+    Exec(String),
+    /// ```focus next```: focus the next client on the current workspace.
+    FocusNext,
+    /// ```focus prev```: focus the previous client on the current workspace.
+    FocusPrev,
+    /// ```swap next```: swap the focused client with the next.
+    SwapNext,
+    /// ```swap prev```: swap the focused client with the previous.
+    SwapPrev,
+    /// ```swap``` or ```swap master```: swap the focused client with the first (master) client.
+    SwapMaster,
+    /// ```toggle floating```: float or tile the focused client.
+    ToggleFloating,
+    /// ```toggle fullscreen```: cover the whole screen with the focused client, or restore it.
+    ToggleFullscreen,
+    /// ```workspace <n>``` or ```workspace switch <n>```: view workspace n (counting from 1).
+    Workspace(usize),
+    /// ```workspace move <n>```: move the focused client to workspace n (counting from 1).
+    MoveToWorkspace(usize),
+    /// ```workspace follow <n>```: move the focused client to workspace n, and view it there.
+    FollowToWorkspace(usize),
+    /// ```workspace previous``` or ```workspace back```: view the previously viewed workspace.
+    PreviousWorkspace,
+    /// ```view <n>```: show only the clients tagged with tag n (counting from 1).
+    View(usize),
+    /// ```toggleview <n>```: show or hide the clients tagged with tag n.
+    ToggleView(usize),
+    /// ```tag <n>```: give the focused client tag n, in place of its other tags.
+    Tag(usize),
+    /// ```toggletag <n>```: add tag n to, or remove it from, the focused client.
+    ToggleTag(usize),
+    /// ```monitor focus next```: focus the next monitor, rightwards.
+    FocusNextMonitor,
+    /// ```monitor focus prev```: focus the previous monitor, leftwards.
+    FocusPrevMonitor,
+    /// ```monitor send next```: move the focused client to the next monitor.
+    SendNextMonitor,
+    /// ```monitor send prev```: move the focused client to the previous monitor.
+    SendPrevMonitor,
+    /// ```master grow```: widen the master area of the current workspace.
+    MasterGrow,
+    /// ```master shrink```: narrow the master area of the current workspace.
+    MasterShrink,
+    /// ```master more```: move one more client into the master area.
+    MasterMore,
+    /// ```master fewer```: move one client out of the master area.
+    MasterFewer,
+    /// ```gaps grow```: widen the inner and outer gaps of the current workspace.
+    GapsGrow,
+    /// ```gaps shrink```: narrow the inner and outer gaps of the current workspace.
+    GapsShrink,
+    /// ```gaps toggle```: hide or restore the gaps of the current workspace.
+    GapsToggle,
+    /// ```layout cycle```: use the next layout on the current workspace.
+    CycleLayout,
+    /// ```reload```: re-read the configuration file.
+    Reload,
+    /// ```quit```: exit Rdwm.
+    Quit,
+}
+
+/// A string that does not name a built-in operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownOperation(pub String);
+
+impl fmt::Display for UnknownOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown operation {:?}", self.0)
+    }
+}
+
+impl FromStr for Operation {
+    type Err = UnknownOperation;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unknown = || UnknownOperation(s.into());
+
+        /* exec keeps its argument verbatim, as it is a shell script */
+        let mut split = s.splitn(2, char::is_whitespace);
+        if split.next().map(|w| w.eq_ignore_ascii_case("exec")) == Some(true) {
+            let command = split.next().map(str::trim).unwrap_or_default();
+            return if command.is_empty() {
+                Err(unknown())
+            } else {
+                Ok(Operation::Exec(command.into()))
+            };
+        }
+
+        let words: Vec<String> = s.split_whitespace().map(str::to_lowercase).collect();
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        let number = |n: &str| match n.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(unknown()),
+        };
+
+        match words.as_slice() {
+            ["kill", "focus"] => Ok(Operation::KillFocus),
+            ["kill", "all"] => Ok(Operation::KillAll),
+            ["focus", "next"] => Ok(Operation::FocusNext),
+            ["focus", "prev"] => Ok(Operation::FocusPrev),
+            ["swap", "next"] => Ok(Operation::SwapNext),
+            ["swap", "prev"] => Ok(Operation::SwapPrev),
+            ["swap"] | ["swap", "master"] => Ok(Operation::SwapMaster),
+            ["toggle", "floating"] => Ok(Operation::ToggleFloating),
+            ["toggle", "fullscreen"] => Ok(Operation::ToggleFullscreen),
+            ["workspace", "previous"] | ["workspace", "back"] => Ok(Operation::PreviousWorkspace),
+            ["workspace", n] | ["workspace", "switch", n] => number(n).map(Operation::Workspace),
+            ["workspace", "move", n] => number(n).map(Operation::MoveToWorkspace),
+            ["workspace", "follow", n] => number(n).map(Operation::FollowToWorkspace),
+            ["view", n] => number(n).map(Operation::View),
+            ["toggleview", n] => number(n).map(Operation::ToggleView),
+            ["tag", n] => number(n).map(Operation::Tag),
+            ["toggletag", n] => number(n).map(Operation::ToggleTag),
+            ["monitor", "focus", "next"] => Ok(Operation::FocusNextMonitor),
+            ["monitor", "focus", "prev"] => Ok(Operation::FocusPrevMonitor),
+            ["monitor", "send", "next"] => Ok(Operation::SendNextMonitor),
+            ["monitor", "send", "prev"] => Ok(Operation::SendPrevMonitor),
+            ["master", "grow"] => Ok(Operation::MasterGrow),
+            ["master", "shrink"] => Ok(Operation::MasterShrink),
+            ["master", "more"] => Ok(Operation::MasterMore),
+            ["master", "fewer"] => Ok(Operation::MasterFewer),
+            ["gaps", "grow"] => Ok(Operation::GapsGrow),
+            ["gaps", "shrink"] => Ok(Operation::GapsShrink),
+            ["gaps", "toggle"] => Ok(Operation::GapsToggle),
+            ["layout", "cycle"] => Ok(Operation::CycleLayout),
+            ["reload"] => Ok(Operation::Reload),
+            ["quit"] => Ok(Operation::Quit),
+            _ => Err(unknown()),
+        }
+    }
+}
+
+#[test]
+pub fn parse_operations() {
+    assert_eq!("kill focus".parse(), Ok(Operation::KillFocus));
+    assert_eq!(" Kill  All ".parse(), Ok(Operation::KillAll));
+    assert_eq!(
+        "exec scrot -s '%Y'".parse(),
+        Ok(Operation::Exec("scrot -s '%Y'".into()))
+    );
+    assert_eq!("workspace 2".parse(), Ok(Operation::Workspace(2)));
+    assert_eq!(
+        "workspace move 3".parse(),
+        Ok(Operation::MoveToWorkspace(3))
+    );
+
+    assert_eq!(
+        "workspace follow 4".parse(),
+        Ok(Operation::FollowToWorkspace(4))
+    );
+    assert_eq!("workspace back".parse(), Ok(Operation::PreviousWorkspace));
+    assert_eq!("toggletag 5".parse(), Ok(Operation::ToggleTag(5)));
+    assert_eq!("monitor send prev".parse(), Ok(Operation::SendPrevMonitor));
+    assert_eq!(" gaps Toggle".parse(), Ok(Operation::GapsToggle));
+
+    assert!("exec".parse::<Operation>().is_err());
+    assert!("workspace 0".parse::<Operation>().is_err());
+    assert!("term".parse::<Operation>().is_err());
+}
@@ -0,0 +1,208 @@
+//! Discovery of the outputs (monitors) that make up the X screen, through the RandR extension where
+//! available, falling back to Xinerama, and then to the whole screen as a single output.
+//! Both extensions are loaded at runtime, so that Rdwm still runs (on one output) without them.
+//! Outputs are queried through Xlib, by ```XlibBackend```.
+use super::layout::Quad;
+use libc::*;
+use std::fmt;
+use x11::xlib::*;
+use x11_dl::xinerama::Xlib as Xinerama;
+use x11_dl::xrandr::{self, Xrandr};
+
+/// The oldest RandR version whose outputs are used.
+const RANDR_VERSION: (c_int, c_int) = (1, 3);
+
+/// Where output geometries come from.
+enum Source {
+    /// RandR, which also reports outputs being added, removed or reconfigured.
+    RandR {
+        xrandr: Box<Xrandr>,
+        event_base: c_int,
+    },
+    /// Xinerama, for servers without RandR 1.3. Outputs are only queried at startup.
+    Xinerama(Xinerama),
+    /// Neither extension; the whole screen is a single output.
+    Screen,
+}
+
+/// The outputs of the default screen.
+pub struct Outputs {
+    source: Source,
+}
+
+impl fmt::Debug for Outputs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let source = match self.source {
+            Source::RandR { .. } => "RandR",
+            Source::Xinerama(_) => "Xinerama",
+            Source::Screen => "Screen",
+        };
+        f.debug_struct("Outputs").field("source", &source).finish()
+    }
+}
+
+impl Outputs {
+    /// Loads RandR, or failing that Xinerama, and registers for RandR screen change events on the
+    /// root window.
+    pub fn open(display: *mut Display, root: Window) -> Self {
+        let dpy = display as *mut x11_dl::xlib::Display;
+
+        let source = match Xrandr::open() {
+            Ok(xrandr) => unsafe {
+                /* Safe as the extension functions were loaded above, and the display is open */
+                let (mut event_base, mut error_base) = (0, 0);
+                let (mut major, mut minor) = (0, 0);
+                if (xrandr.XRRQueryExtension)(dpy, &mut event_base, &mut error_base) == 0 {
+                    None
+                } else if (xrandr.XRRQueryVersion)(dpy, &mut major, &mut minor) == 0
+                    || (major, minor) < RANDR_VERSION
+                {
+                    /* XRRGetScreenResourcesCurrent is new in 1.3 */
+                    debug!("RandR {}.{} is older than 1.3", major, minor);
+                    None
+                } else {
+                    (xrandr.XRRSelectInput)(dpy, root, xrandr::RRScreenChangeNotifyMask);
+                    Some(Source::RandR {
+                        xrandr: Box::new(xrandr),
+                        event_base,
+                    })
+                }
+            },
+            Err(err) => {
+                debug!("RandR unavailable: {}", err);
+                None
+            }
+        };
+
+        let source = source
+            .or_else(|| match Xinerama::open() {
+                /* Safe as the extension functions were loaded, and the display is open */
+                Ok(xinerama) if unsafe { (xinerama.XineramaIsActive)(dpy) } != 0 => {
+                    Some(Source::Xinerama(xinerama))
+                }
+                Ok(_) => None,
+                Err(err) => {
+                    debug!("Xinerama unavailable: {}", err);
+                    None
+                }
+            })
+            .unwrap_or(Source::Screen);
+
+        let outputs = Outputs { source };
+        info!("Using outputs from {:?}", outputs);
+        outputs
+    }
+
+    /// The type of event that reports that outputs changed, if outputs are ever reported to change.
+    pub fn screen_change_event(&self) -> Option<c_int> {
+        match self.source {
+            Source::RandR { event_base, .. } => Some(event_base + xrandr::RRScreenChangeNotify),
+            _ => None,
+        }
+    }
+
+    /// Updates Xlib's idea of the screen size after a screen change event.
+    pub fn update(&self, event: &mut XEvent) {
+        if let Source::RandR { xrandr, .. } = &self.source {
+            unsafe {
+                /* Safe as x11-dl's XEvent has the same layout as Xlib's */
+                (xrandr.XRRUpdateConfiguration)(event as *mut XEvent as *mut _);
+            }
+        }
+    }
+
+    /// Returns the geometry of every active output, as ordered by ```unique```.
+    /// Falls back to the whole screen if no output is found.
+    pub fn query(&self, display: *mut Display, root: Window) -> Vec<Quad> {
+        let dpy = display as *mut x11_dl::xlib::Display;
+        let mut screens = Vec::new();
+
+        match &self.source {
+            Source::RandR { xrandr, .. } => unsafe {
+                /* Safe as resources hold ncrtc CRTCs, and each is freed after use */
+                let resources = (xrandr.XRRGetScreenResourcesCurrent)(dpy, root);
+                if !resources.is_null() {
+                    let crtcs = if (*resources).ncrtc > 0 {
+                        std::slice::from_raw_parts((*resources).crtcs, (*resources).ncrtc as usize)
+                    } else {
+                        &[]
+                    };
+                    for crtc in crtcs {
+                        let info = (xrandr.XRRGetCrtcInfo)(dpy, resources, *crtc);
+                        if info.is_null() {
+                            continue;
+                        }
+                        /* CRTCs without a mode are disabled */
+                        if (*info).mode != 0 {
+                            screens.push(Quad {
+                                x: (*info).x.max(0) as u32,
+                                y: (*info).y.max(0) as u32,
+                                w: (*info).width,
+                                h: (*info).height,
+                            });
+                        }
+                        (xrandr.XRRFreeCrtcInfo)(info);
+                    }
+                    (xrandr.XRRFreeScreenResources)(resources);
+                }
+            },
+            Source::Xinerama(xinerama) => unsafe {
+                /* Safe as XineramaQueryScreens returns number screens, freed with XFree */
+                let mut number = 0;
+                let info = (xinerama.XineramaQueryScreens)(dpy, &mut number);
+                if !info.is_null() {
+                    for screen in std::slice::from_raw_parts(info, number.max(0) as usize) {
+                        screens.push(Quad {
+                            x: screen.x_org.max(0) as u32,
+                            y: screen.y_org.max(0) as u32,
+                            w: screen.width.max(0) as u32,
+                            h: screen.height.max(0) as u32,
+                        });
+                    }
+                    XFree(info as *mut c_void);
+                }
+            },
+            Source::Screen => (),
+        }
+
+        let screens = unique(screens);
+        if screens.is_empty() {
+            /* Safe as the display is open */
+            unsafe {
+                let screen = XDefaultScreen(display);
+                vec![Quad::from_size(
+                    XDisplayHeight(display, screen) as u32,
+                    XDisplayWidth(display, screen) as u32,
+                )]
+            }
+        } else {
+            screens
+        }
+    }
+}
+
+/// Drops empty and duplicate (ie. mirrored) outputs, and orders the rest left to right, then top to
+/// bottom, such that ```monitor focus next``` moves rightwards.
+pub fn unique(mut screens: Vec<Quad>) -> Vec<Quad> {
+    screens.retain(|s| s.w > 0 && s.h > 0);
+    screens.sort_by_key(|s| (s.x, s.y, s.w, s.h));
+    screens.dedup();
+    screens
+}
+
+#[test]
+pub fn unique_outputs() {
+    let left = Quad::from_size(1080, 1920);
+    let right = Quad {
+        x: 1920,
+        y: 0,
+        w: 2560,
+        h: 1440,
+    };
+
+    assert_eq!(
+        unique(vec![right, left, Quad::default(), right]),
+        vec![left, right]
+    );
+    assert!(unique(vec![Quad::default()]).is_empty());
+}
@@ -0,0 +1,48 @@
+use libc::*;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+/// Runs a user-supplied command as a POSIX shell script, ie. ```/bin/sh -c <action>```.
+///
+/// The child is placed in a new session so that it outlives Rdwm (and is not sent its terminal's
+/// signals), and the given file descriptor - the X connection - is closed so that the child cannot
+/// hold it open. Children are reaped by ```reap``` once they exit.
+pub fn spawn(action: &str, x_fd: c_int) -> io::Result<pid_t> {
+    let mut command = Command::new("/bin/sh");
+    command.arg("-c").arg(action);
+
+    unsafe {
+        /* Safe as only async-signal-safe functions are called between fork and exec */
+        command.pre_exec(move || {
+            if setsid() < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            close(x_fd);
+            Ok(())
+        });
+    }
+
+    let child = command.spawn()?;
+    debug!("Spawned process {} for {:?}", child.id(), action);
+    Ok(child.id() as pid_t)
+}
+
+/// Waits upon every child that has exited, logging its exit status, so that none are left as
+/// zombies. Never blocks; intended to be called upon SIGCHLD.
+pub fn reap() {
+    loop {
+        let mut status = 0;
+        /* Safe as status outlives the call */
+        let pid = unsafe { waitpid(-1, &mut status, WNOHANG) };
+        if pid <= 0 {
+            break;
+        }
+
+        if WIFEXITED(status) {
+            debug!("Process {} exited with status {}", pid, WEXITSTATUS(status));
+        } else if WIFSIGNALED(status) {
+            debug!("Process {} killed by signal {}", pid, WTERMSIG(status));
+        }
+    }
+}
@@ -1,23 +1,70 @@
 #![allow(dead_code)]
 
-use super::config::Config;
+use super::backend::*;
+use super::colour::Palette;
+use super::config::{Arrangement, Borders, Config, EffectiveConfig, Model, MAX_GAP};
+use super::keys::KeyCombo;
+use super::layout::{Gaps, Layout, Layouts, Params, Quad};
+use super::operation::Operation;
+use super::process;
+use super::selection::ManagerSelection;
+use super::signal::Signals;
+use super::watch::FileWatch;
 use libc::*;
-use std::sync::Mutex;
-use x11::xlib::*;
-type XWindow = x11::xlib::Window; // TODO NewType pattern to prevent i32 aliasing issues
-
-lazy_static! {
-    /// Lazily evaluated Mutex used to guard global error state required by Xlib error handler registration.
-    /// It's not an ideal way to handle global state (even if it was changed to a more performant RefCell
-    /// but will do for the time being.
-    static ref WM_DETECTED: Mutex<bool> = Mutex::new(false);
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Change in master area ratio per ```master grow``` or ```master shrink``` operation.
+const MASTER_RATIO_STEP: f64 = 0.05;
+/// Pixels added to or removed from both gaps by ```gaps grow``` and ```gaps shrink```.
+const GAP_STEP: u32 = 2;
+
+/// dwm-style tags carried by a client, one bit per tag, when ```[workspaces]``` uses
+/// ```model = "tags"```. A client is visible when it shares a tag with its workspace's view.
+type TagMask = u32;
+
+/// Every tag; the view of every workspace, and the tags of every client, in the workspace model.
+const ALL_TAGS: TagMask = TagMask::MAX;
+
+/// ICCCM ```WM_STATE``` values (ICCCM 4.1.3.1), which Xlib does not define.
+const WITHDRAWN_STATE: c_long = 0;
+const NORMAL_STATE: c_long = 1;
+
+/// Root window property holding the current workspace's layout symbol, eg. ```[3/7]``` when the
+/// third of seven clients is shown in monocle, for bars to show.
+const LAYOUT_PROPERTY: &str = "_RDWM_LAYOUT";
+
+/// Atoms interned once, at startup.
+#[derive(Debug, Clone, Copy)]
+struct Atoms {
+    wm_protocols: Atom,
+    wm_delete_window: Atom,
+    wm_state: Atom,
+    net_wm_ping: Atom,
+    utf8_string: Atom,
+    rdwm_layout: Atom,
+}
+
+impl Atoms {
+    fn intern(backend: &dyn Backend) -> Self {
+        Atoms {
+            wm_protocols: backend.intern_atom("WM_PROTOCOLS"),
+            wm_delete_window: backend.intern_atom("WM_DELETE_WINDOW"),
+            wm_state: backend.intern_atom("WM_STATE"),
+            net_wm_ping: backend.intern_atom("_NET_WM_PING"),
+            utf8_string: backend.intern_atom("UTF8_STRING"),
+            rdwm_layout: backend.intern_atom(LAYOUT_PROPERTY),
+        }
+    }
 }
 
 bitflags! {
     /// 'Internal' bitflags (ie. not known to X) used to manage opt-in and default Client application logic.
     /// For example, the current state of a window to colour borders correctly, override tiling rules, etc.
     struct WindowFlags: u32 {
-        const NONE         = 0 << 0;
+        const NONE         = 0;
         const TILING       = 1 << 0;
         const FLOATING     = 1 << 1;
         const URGENT       = 1 << 2;
@@ -27,68 +74,164 @@ bitflags! {
     }
 }
 
-/// Window manager that intercepts XEvents in the main event loop, propagating them to appropriate agents.
+/// Window manager that intercepts events in the main event loop, propagating them to appropriate agents.
 /// Maintains an XWindow handle registered for Substructure Redirection, as well as a collection of Workspaces
-/// which hold client windows.
+/// which hold client windows. The display server is only reached through a ```Backend```.
 #[derive(Debug)]
 pub struct Rdwm {
-    display: *mut Display,
-    root: XWindow,
+    backend: Rc<dyn Backend>,
+    root: WindowId,
     workspaces: Vec<Workspace>,
     current: usize,
-    config: Config,
+    previous: usize,
+    monitors: Vec<Monitor>,
+    monitor: usize,
+    atoms: Atoms,
+    selection: ManagerSelection,
+    closing: Vec<Closing>,
+    model: Model,
+    tags: usize,
+    config: EffectiveConfig,
+    bindings: Vec<(KeyCombo, Operation)>,
+    numlock: c_uint,
+    running: bool,
+    signals: Signals,
+    config_path: Option<PathBuf>,
+    watch: Option<FileWatch>,
+    palette: Palette,
+    layouts: Layouts,
+    /// The layout symbol last published in ```LAYOUT_PROPERTY```.
+    layout_symbol: String,
 }
 
 impl Rdwm {
-    /// Instantiates a substructure redirected X client, with a single empty workspace.
+    /// Instantiates a substructure redirected X client, with one empty workspace for each configured
+    /// workspace name, the first of which is shown.
     /// Refutable as there may already be an X client registered for substructure redirection (ie.
-    /// another window manager).
-    pub fn init() -> Option<Self> {
-        let display = unsafe {
-            /* Safe because no side effects at this point */
-            XOpenDisplay(std::ptr::null())
-        };
-
-        if display.is_null() {
-            return None;
+    /// another window manager), which is detected before anything else is done with the display.
+    /// An explicit configuration file may be given, otherwise the XDG base directories are searched.
+    /// With ```replace```, a running ICCCM-compliant window manager is asked to exit first.
+    /// Configured layouts are looked up in ```layouts```, eg. ```Layouts::builtin```.
+    pub fn init(
+        backend: Rc<dyn Backend>,
+        layouts: Layouts,
+        config: Option<&Path>,
+        replace: bool,
+    ) -> Result<Self, StartupError> {
+        /* The manager selection comes first, so that a replaced window manager has released the
+         * root window before its substructure is redirected */
+        let selection = backend.acquire_selection(replace)?;
+        if !backend.redirect_root() {
+            return Err(StartupError::AnotherWm);
         }
-        let screen = unsafe { XScreenOfDisplay(display, 0) };
 
-        if screen.is_null() {
-            trace!("No screens associated with display");
-            return None;
-        }
+        let signals = Signals::install(&[SIGCHLD, SIGHUP]).map_err(StartupError::Signals)?;
+        /* Children may have been inherited, eg. from .xinitrc */
+        process::reap();
+
+        let config_path = config.map(Path::to_path_buf);
+        let watch = Config::watch_path(config).and_then(|file| watch(&file));
 
-        let config = Config::get_config();
-        let root = Rdwm::register_root(&config, display);
+        let config = EffectiveConfig::resolve(Config::get_config(config, &*backend, &layouts));
+        let mut rdwm = Rdwm::new(backend, selection, signals, config, layouts);
+        rdwm.config_path = config_path;
+        rdwm.watch = watch;
+
+        Ok(rdwm)
+    }
 
-        let mut workspaces = Vec::new();
-        let cur_workspace = unsafe {
-            Workspace::init(
+    /// Manages the screen with an already resolved configuration: grabs keybindings, and fits a
+    /// monitor (and workspace) to each of the backend's outputs.
+    fn new(
+        backend: Rc<dyn Backend>,
+        selection: ManagerSelection,
+        signals: Signals,
+        config: EffectiveConfig,
+        layouts: Layouts,
+    ) -> Self {
+        let root = backend.root();
+        let palette = Palette::alloc(&*backend, &config.borders);
+        let screens = backend.outputs();
+
+        /* In the tag model, each monitor's workspace is created along with the monitor */
+        let model = config.workspaces.model;
+        let (workspaces, tags) = match model {
+            Model::Workspaces => (
+                config
+                    .workspaces
+                    .names
+                    .iter()
+                    .enumerate()
+                    .map(|(number, name)| {
+                        Workspace::init(number, name, Quad::default(), &config, &layouts)
+                    })
+                    .collect(),
                 0,
-                Quad::from_size((*screen).height as u32, (*screen).width as u32),
-            )
+            ),
+            Model::Tags => (Vec::new(), config.workspaces.names.len()),
         };
 
-        workspaces.push(cur_workspace);
-
-        Some(Rdwm {
-            display,
+        let mut rdwm = Rdwm {
+            atoms: Atoms::intern(&*backend),
+            backend,
             root,
             workspaces,
             current: 0,
+            previous: 0,
+            monitors: Vec::new(),
+            monitor: 0,
+            selection,
+            closing: Vec::new(),
+            model,
+            tags,
             config,
-        })
+            bindings: Vec::new(),
+            numlock: 0,
+            running: true,
+            signals,
+            config_path: None,
+            watch: None,
+            palette,
+            layouts,
+            layout_symbol: String::new(),
+        };
+        rdwm.grab_keys();
+        rdwm.apply_screens(screens);
+        rdwm
     }
 
-    /// Returns a handle to an X display acting as the root window, registered for any configuration
-    /// required by Rdwm consumers.
-    fn register_root(_config: &Config, display: *mut Display) -> XWindow {
-        // TODO
-        unsafe {
-            let root = XDefaultRootWindow(display);
-            XSelectInput(display, root, KeyPressMask); // TODO
-            root
+    /// Grabs every configured keybinding on the root window, replacing any previous grabs.
+    /// A binding's operation refers either to a user-supplied command by name or, failing that, to a
+    /// built-in operation. Bindings whose keys are not on the current keyboard are skipped.
+    fn grab_keys(&mut self) {
+        let commands = &self.config.commands;
+        let resolve = |name: &str| match commands.iter().find(|c| c.name == name) {
+            Some(command) => Ok(Operation::Exec(command.action.clone())),
+            None => name.parse::<Operation>().map_err(|err| err.to_string()),
+        };
+
+        self.numlock = self.backend.numlock_mask();
+        self.bindings = self
+            .config
+            .bindings
+            .iter()
+            .filter_map(|b| {
+                let combo = KeyCombo::parse(&b.keys, &*self.backend).map_err(|err| err.to_string());
+                match combo.and_then(|combo| Ok((combo, resolve(&b.operation)?))) {
+                    Ok(binding) => Some(binding),
+                    Err(err) => {
+                        warn!("Not binding {:?}: {}", b.keys, err);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        self.backend.ungrab_keys(self.root);
+        for (combo, operation) in &self.bindings {
+            if !combo.grab(&*self.backend, self.root, self.numlock) {
+                warn!("No key on this keyboard for binding to {:?}", operation);
+            }
         }
     }
 
@@ -106,222 +249,881 @@ impl Rdwm {
         self.workspaces.get_mut(self.current)
     }
 
-    /// Begins the main event loop.
-    /// Registers for error handling, input selection and synchronizes with the X server.
-    pub fn run(&mut self) {
-        unsafe {
-            /* Sound, as panics on errors that aren't handled properly yet */
-            XSetErrorHandler(Some(Rdwm::on_wm_detected));
+    /// Begins the main event loop, after framing any windows that existed before Rdwm started.
+    /// Refutable as the existing windows may not be queryable.
+    pub fn run(&mut self) -> Result<(), StartupError> {
+        self.backend.grab_server();
+        let existing = match self.backend.existing_windows() {
+            Some(existing) => existing,
+            None => {
+                self.backend.ungrab_server();
+                return Err(StartupError::QueryTree);
+            }
+        };
 
-            /* We want to register reparenting for root window - If erroneous, handler will notify & exit */
-            XSelectInput(
-                self.display,
-                self.root,
-                SubstructureRedirectMask | SubstructureNotifyMask | FocusChangeMask,
-            );
+        // Frame existing windows from the saved set
+        for w in existing.iter() {
+            self.frame(w, true);
+        }
+        self.backend.ungrab_server();
+
+        while self.running {
+            /* Checked every iteration, as a busy event queue may never let wait() block */
+            self.expire_closing();
+            self.handle_wakeups();
+            self.publish_layout();
+            if !self.backend.pending() {
+                self.wait();
+                continue;
+            }
 
-            XSync(self.display, false as c_int);
-
-            /* MaybeUninit is safe because XQueryTree will always write _something_ */
-            XGrabServer(self.display);
-            let mut existing_root = std::mem::MaybeUninit::<XWindow>::zeroed().assume_init();
-            let mut existing_parent = std::mem::MaybeUninit::<XWindow>::zeroed().assume_init();
-            let mut existing_windows =
-                std::mem::MaybeUninit::<*mut XWindow>::zeroed().assume_init();
-            let mut num_existing = std::mem::MaybeUninit::<c_uint>::zeroed().assume_init();
-
-            assert!(
-                XQueryTree(
-                    self.display,
-                    self.root,
-                    &mut existing_root,
-                    &mut existing_parent,
-                    &mut existing_windows,
-                    &mut num_existing
-                ) != false as c_int,
-                "Could not obtain existing query tree"
-            );
+            let event = self.backend.next_event();
+            self.dispatch(event);
+        }
 
-            trace!(
-                "Root: {:#?} Parent: {:#?} Windows: {:#?} Number of existing: {:#?}",
-                existing_root,
-                existing_parent,
-                existing_windows,
-                num_existing
-            );
+        Ok(())
+    }
 
-            assert_eq!(existing_root, self.root);
+    /// Routes an event to its handler. Events that the backend does not decode, or that are of no
+    /// interest to Rdwm, arrive as ```Event::Ignored```.
+    fn dispatch(&mut self, event: Event) {
+        match event {
+            Event::KeyPress { keycode, state } => self.on_key_press(keycode, state),
+            Event::ButtonPress { window } => self.on_button_press(window),
+            Event::EnterNotify { window } => self.on_enter_notify(window),
+            Event::LeaveNotify { window } => self.on_leave(window),
+            Event::FocusIn { window } | Event::FocusOut { window } => self.on_focus_in(window),
+            Event::CreateNotify { window } => self.on_create_notify(window),
+            Event::DestroyNotify { window } => self.on_destroy_notify(window),
+            Event::UnmapNotify { window, synthetic } => self.on_unmap_notify(window, synthetic),
+            Event::MapNotify { window } => self.on_map_notify(window),
+            Event::MapRequest { window } => self.on_map_request(window),
+            Event::ReparentNotify { window, parent } => self.on_reparent_notify(window, parent),
+            Event::ConfigureNotify { window } => self.on_configure_notify(window),
+            Event::ConfigureRequest {
+                window,
+                changes,
+                mask,
+            } => self.on_configure_request(window, &changes, mask),
+            Event::PropertyNotify { window, atom } => self.on_property_notify(window, atom),
+            Event::ClientMessage {
+                window,
+                message_type,
+                data,
+            } => self.on_client_message(window, message_type, &data),
+            Event::SelectionClear { window, selection } => {
+                self.on_selection_clear(window, selection)
+            }
+            Event::KeyboardChanged => self.on_keyboard_change(),
+            Event::ScreenChange => self.on_screen_change(),
+            Event::Ignored => (),
+        }
+    }
+
+    /// Regrabs keybindings when the keyboard, or its mapping, changes, as keycodes or modifiers may
+    /// have moved, eg. after ```xmodmap``` or ```setxkbmap```.
+    fn on_keyboard_change(&mut self) {
+        debug!("Keyboard changed; regrabbing keys");
+        self.grab_keys();
+    }
 
-            // Frame existing windows from the saved set
-            let existing = std::slice::from_raw_parts(existing_windows, num_existing as usize);
-            for w in existing.iter() {
-                self.frame(w, true);
+    fn on_property_notify(&self, window: WindowId, atom: Atom) {
+        trace!("OnPropertyNotify event: {:#?} {:#?}", window, atom);
+    }
+
+    /// Handles replies to ```_NET_WM_PING```, which clients send to the root window. A client that
+    /// replies while being closed is responsive, eg. asking whether to save changes, so is left to
+    /// close itself rather than being killed.
+    fn on_client_message(&mut self, window: WindowId, message_type: Atom, data: &[c_long; 5]) {
+        trace!(
+            "OnClientMessage event: {:#?} {:#?} {:?}",
+            window,
+            message_type,
+            data
+        );
+
+        let protocol = data[0] as Atom;
+        if window == self.root
+            && message_type == self.atoms.wm_protocols
+            && protocol == self.atoms.net_wm_ping
+        {
+            let window = data[2] as WindowId;
+            if self.closing.iter().any(|c| c.window == window) {
+                info!(
+                    "Client {} is responding; leaving it to close itself",
+                    window
+                );
+                self.closing.retain(|c| c.window != window);
             }
+        }
+    }
+
+    /// Exits when another window manager takes the manager selection, eg. with its own
+    /// ```--replace```. Clients are left to the X server, which reparents them to the root window.
+    fn on_selection_clear(&mut self, window: WindowId, selection: Atom) {
+        trace!("OnSelectionClear event: {:#?} {:#?}", window, selection);
+
+        if self.selection.is_lost(selection, window) {
+            info!("Replaced by another window manager; exiting");
+            self.running = false;
+        }
+    }
+
+    fn on_create_notify(&self, window: WindowId) {
+        trace!("OnCreateNotify event: {:#?}", window);
+    }
+
+    /// Unmanages a client destroyed without first being unmapped. Clients that did unmap are
+    /// already unmanaged, and destroyed frames are not clients, so both are ignored.
+    fn on_destroy_notify(&mut self, window: WindowId) {
+        trace!("OnDestroyNotify event: {:#?}", window);
+
+        match self.find_client(window) {
+            Some((workspace, num)) => self.unmanage(workspace, num, true),
+            None => trace!("Ignoring DestroyNotify for unmanaged window {}", window),
+        }
+    }
+
+    fn on_reparent_notify(&self, window: WindowId, parent: WindowId) {
+        trace!("OnReparentNotify event: {:#?} to {:#?}", window, parent);
+    }
+
+    fn on_map_notify(&self, window: WindowId) {
+        trace!("OnMapNotify event: {:#?}", window);
+    }
 
-            XFree(existing_windows as *mut _ as *mut c_void);
-            XUngrabServer(self.display);
+    fn on_configure_notify(&self, window: WindowId) {
+        trace!("OnConfigureNotify event: {:#?}", window);
+    }
 
-            loop {
-                if *WM_DETECTED.lock().unwrap() == true {
+    fn on_key_press(&mut self, keycode: Keycode, state: c_uint) {
+        trace!("OnKeyPress event: {:#?} {:#?}", keycode, state);
+
+        if let Some((_, operation)) = self
+            .bindings
+            .iter()
+            .find(|(combo, _)| combo.matches(&*self.backend, keycode, state, self.numlock))
+        {
+            let operation = operation.clone();
+            self.execute(operation);
+        }
+    }
+
+    /// Performs an operation, regardless of how it was requested (eg. keybinding or IPC).
+    pub fn execute(&mut self, operation: Operation) {
+        debug!("Execute: {:?}", operation);
+
+        /* In the tag model, workspace operations view and tag a single tag */
+        let operation = match (self.model, operation) {
+            (Model::Tags, Operation::Workspace(number)) => Operation::View(number),
+            (Model::Tags, Operation::MoveToWorkspace(number)) => Operation::Tag(number),
+            (Model::Tags, Operation::FollowToWorkspace(number)) => {
+                self.execute(Operation::Tag(number));
+                Operation::View(number)
+            }
+            (_, operation) => operation,
+        };
+        let backend = Rc::clone(&self.backend);
+        let borders = self.config.borders.clone();
+        let palette = self.palette.clone();
+
+        match operation {
+            Operation::KillFocus => {
+                if let Some(client) = self.get_current().and_then(|w| w.get_selected()) {
+                    self.close(client.context.id);
+                }
+            }
+            Operation::KillAll => {
+                let windows: Vec<WindowId> = self
+                    .get_current()
+                    .iter()
+                    .flat_map(|w| w.visible())
+                    .map(|(_, c)| c.context.id)
+                    .collect();
+                for window in windows {
+                    self.close(window);
+                }
+            }
+            Operation::Exec(action) => self.spawn(&action),
+            Operation::FocusNext | Operation::FocusPrev => {
+                if let Some(workspace) = self.get_mut_current() {
+                    let forward = operation == Operation::FocusNext;
+                    if let Some(index) = workspace.relative(forward) {
+                        workspace.update_selected(&*backend, &palette, index);
+                    }
+                }
+            }
+            Operation::SwapNext | Operation::SwapPrev | Operation::SwapMaster => {
+                if let Some(workspace) = self.get_mut_current() {
+                    let other = match operation {
+                        Operation::SwapMaster => workspace.visible().map(|(num, _)| num).next(),
+                        _ => workspace.relative(operation == Operation::SwapNext),
+                    };
+                    if let Some(other) = other.filter(|_| workspace.get_selected().is_some()) {
+                        workspace.clients.swap(workspace.selected, other);
+                        workspace.selected = other;
+                        workspace.arrange(&*backend);
+                    }
+                }
+            }
+            Operation::ToggleFloating | Operation::ToggleFullscreen => {
+                let flag = if operation == Operation::ToggleFloating {
+                    WindowFlags::FLOATING
+                } else {
+                    WindowFlags::FULLSCREEN
+                };
+                if let Some(workspace) = self.get_mut_current() {
+                    if let Some(client) = workspace.get_mut_selected() {
+                        client.flags.toggle(flag);
+                        if !client.flags.contains(WindowFlags::FULLSCREEN) {
+                            backend.set_border_width(client.frame.id, borders.size as u32);
+                        }
+                    }
+                    workspace.arrange(&*backend);
+                }
+            }
+            Operation::Workspace(number)
+            | Operation::MoveToWorkspace(number)
+            | Operation::FollowToWorkspace(number) => {
+                if number > self.workspaces.len() {
+                    warn!("No such workspace: {}", number);
+                    return;
+                }
+                match operation {
+                    Operation::Workspace(_) => self.view(number - 1),
+                    Operation::MoveToWorkspace(_) => self.move_selected(number - 1, false),
+                    _ => self.move_selected(number - 1, true),
+                }
+            }
+            Operation::PreviousWorkspace => match self.model {
+                Model::Workspaces => self.view(self.previous),
+                Model::Tags => {
+                    if let Some(workspace) = self.get_mut_current() {
+                        workspace.view_tags(workspace.previous_view);
+                        workspace.arrange(&*backend);
+                    }
+                    self.focus_current();
+                }
+            },
+            Operation::View(number)
+            | Operation::ToggleView(number)
+            | Operation::Tag(number)
+            | Operation::ToggleTag(number) => {
+                if self.model != Model::Tags {
+                    warn!("{:?} needs [workspaces] model = \"tags\"", operation);
+                    return;
+                }
+                if number > self.tags {
+                    warn!("No such tag: {}", number);
                     return;
                 }
 
-                let mut event: XEvent = { std::mem::MaybeUninit::<XEvent>::zeroed().assume_init() };
-
-                XNextEvent(self.display, &mut event);
-
-                #[allow(non_upper_case_globals)]
-                /* Safe because we know that the type of event dictates well-defined union member access */
-                match event.get_type() {
-                    /* TODO */
-                    KeyPress => self.on_key_press(&event.key),
-                    //  KeyRelease =>
-                    ButtonPress => self.on_button_press(&event.button),
-                    //  ButtonRelease =>
-                    //  MotionNotify =>
-                    EnterNotify => self.on_enter_notify(&event.crossing),
-                    LeaveNotify => self.on_leave(&event.crossing),
-                    FocusIn => self.on_focus_in(&event.focus_change),
-                    FocusOut => self.on_focus_in(&event.focus_change),
-                    //  KeymapNotify =>
-                    //  Expose =>
-                    //  GraphicsExpose =>
-                    //  NoExpose =>
-                    //  VisibilityNotify =>
-                    CreateNotify => self.on_create_notify(&event),
-                    DestroyNotify => self.on_destroy_notify(&event.destroy_window),
-                    UnmapNotify => self.on_unmap_notify(&event.unmap),
-                    MapNotify => self.on_map_notify(&event.map),
-                    MapRequest => self.on_map_request(&event.map_request),
-                    ReparentNotify => self.on_reparent_notify(&event.reparent),
-                    ConfigureNotify => self.on_configure_notify(&event.configure),
-                    ConfigureRequest => self.on_configure_request(&event.configure_request),
-                    //  GravityNotify =>
-                    //  ResizeRequest =>
-                    //  CirculateNotify =>
-                    //  CirculateRequest =>
-                    //  PropertyNotify =>
-                    //  SelectionClear =>
-                    //  SelectionRequest =>
-                    //  SelectionNotify =>
-                    //  ColormapNotify =>
-                    //  ClientMessage =>
-                    //  MappingNotify =>
-                    //  GenericEvent =>
-                    _ => unimplemented!(),
+                let tag: TagMask = 1 << (number - 1);
+                if let Some(workspace) = self.get_mut_current() {
+                    match operation {
+                        Operation::View(_) => workspace.view_tags(tag),
+                        Operation::ToggleView(_) => workspace.view_tags(workspace.view ^ tag),
+                        Operation::Tag(_) => workspace.tag_selected(tag),
+                        _ => {
+                            let tags = workspace.get_selected().map_or(0, |c| c.tags ^ tag);
+                            workspace.tag_selected(tags);
+                        }
+                    }
+                    workspace.arrange(&*backend);
+                }
+                self.focus_current();
+            }
+            Operation::FocusNextMonitor | Operation::FocusPrevMonitor => {
+                if let Some(index) = self.relative_monitor(operation == Operation::FocusNextMonitor)
+                {
+                    self.select_monitor(index);
+                    self.focus_current();
+                }
+            }
+            Operation::SendNextMonitor | Operation::SendPrevMonitor => {
+                if let Some(index) = self.relative_monitor(operation == Operation::SendNextMonitor)
+                {
+                    self.move_selected(self.monitors[index].workspace, false);
                 }
             }
+            Operation::MasterGrow | Operation::MasterShrink => {
+                if let Some(workspace) = self.get_mut_current() {
+                    let step = if operation == Operation::MasterGrow {
+                        MASTER_RATIO_STEP
+                    } else {
+                        -MASTER_RATIO_STEP
+                    };
+                    let ratio = &mut workspace.params.master_ratio;
+                    *ratio = (*ratio + step).clamp(0.05, 0.95);
+                    workspace.arrange(&*backend);
+                }
+            }
+            Operation::MasterMore | Operation::MasterFewer => {
+                if let Some(workspace) = self.get_mut_current() {
+                    let nmaster = &mut workspace.params.nmaster;
+                    *nmaster = if operation == Operation::MasterMore {
+                        *nmaster + 1
+                    } else {
+                        nmaster.saturating_sub(1)
+                    };
+                    workspace.arrange(&*backend);
+                }
+            }
+            Operation::GapsGrow | Operation::GapsShrink => {
+                if let Some(workspace) = self.get_mut_current() {
+                    let resize = |gap: u32| {
+                        if operation == Operation::GapsGrow {
+                            (gap + GAP_STEP).min(MAX_GAP as u32)
+                        } else {
+                            gap.saturating_sub(GAP_STEP)
+                        }
+                    };
+                    workspace.gaps = Gaps {
+                        inner: resize(workspace.gaps.inner),
+                        outer: resize(workspace.gaps.outer),
+                    };
+                    workspace.show_gaps = true;
+                    workspace.arrange(&*backend);
+                }
+            }
+            Operation::GapsToggle => {
+                if let Some(workspace) = self.get_mut_current() {
+                    workspace.show_gaps = !workspace.show_gaps;
+                    workspace.arrange(&*backend);
+                }
+            }
+            Operation::CycleLayout => {
+                if let Some(workspace) = self.get_mut_current() {
+                    workspace.cycle_layout();
+                    workspace.arrange(&*backend);
+                }
+            }
+            Operation::Reload => self.reload(),
+            Operation::Quit => self.running = false,
         }
     }
 
-    fn on_create_notify(&self, event: &XEvent) {
-        trace!("OnCreateNotify event: {:#?}", *event);
+    /// Shows the workspace at the given index on the current monitor, in place of the current
+    /// workspace, and focuses its selected client. A workspace already shown on another monitor
+    /// swaps places with the current workspace.
+    /// Frames on the old workspace are unmapped, while their clients stay mapped (if no longer
+    /// viewable), so that ```on_unmap_notify``` does not mistake hiding a workspace for clients
+    /// withdrawing.
+    fn view(&mut self, index: usize) {
+        if index == self.current || index >= self.workspaces.len() {
+            return;
+        }
+        let backend = Rc::clone(&self.backend);
+        let screen = self.monitors[self.monitor].screen;
+
+        /* Map the new workspace first, so that the root window does not flash through */
+        let target = &mut self.workspaces[index];
+        target.screen = screen;
+        target.show(&*backend);
+
+        match self.monitors.iter().position(|m| m.workspace == index) {
+            Some(other) => {
+                let old = &mut self.workspaces[self.current];
+                old.screen = self.monitors[other].screen;
+                old.arrange(&*backend);
+                self.monitors[other].workspace = self.current;
+            }
+            None => self.workspaces[self.current].hide(&*backend),
+        }
+
+        self.monitors[self.monitor].workspace = index;
+        self.previous = self.current;
+        self.current = index;
+        self.focus_current();
+
+        debug!(
+            "Viewing workspace {} ({})",
+            index + 1,
+            self.workspaces[index].name
+        );
     }
 
-    fn on_destroy_notify(&self, event: &XDestroyWindowEvent) {
-        trace!("XDestroyWindowEvent event: {:#?}", *event);
+    /// Moves the selected client of the current workspace to the workspace at the given index, then
+    /// views that workspace if ```follow``` is set. In the tag model, the client takes the tags in
+    /// view on the target workspace.
+    fn move_selected(&mut self, index: usize, follow: bool) {
+        if index == self.current || index >= self.workspaces.len() {
+            return;
+        }
+        let backend = Rc::clone(&self.backend);
+        let palette = self.palette.clone();
+
+        let mut client = match self.get_mut_current() {
+            Some(workspace) if workspace.get_selected().is_some() => {
+                workspace.take(workspace.selected)
+            }
+            _ => return,
+        };
+
+        let target = &mut self.workspaces[index];
+        if !follow && !target.shown {
+            backend.unmap(client.frame.id);
+        }
+        if self.model == Model::Tags {
+            client.tags = target.view;
+        }
+        backend.set_border_pixel(client.frame.id, palette.normal);
+        target.clients.push(client);
+        target.selected = target.clients.len() - 1;
+        target.arrange(&*backend);
+
+        if follow {
+            self.view(index);
+        } else {
+            if let Some(workspace) = self.get_current() {
+                workspace.arrange(&*backend);
+            }
+            self.focus_current();
+        }
     }
 
-    fn on_reparent_notify(&self, event: &XReparentEvent) {
-        trace!("OnReparentNotify event: {:#?}", *event);
+    /// Makes the monitor at the given index, and the workspace it shows, current. The selected client
+    /// of the previously current workspace loses its focus colour.
+    fn select_monitor(&mut self, index: usize) {
+        if index == self.monitor || index >= self.monitors.len() {
+            return;
+        }
+
+        let backend = Rc::clone(&self.backend);
+        let normal = self.palette.normal;
+        if let Some(client) = self.get_current().and_then(Workspace::get_selected) {
+            backend.set_border_pixel(client.frame.id, normal);
+        }
+
+        self.monitor = index;
+        self.current = self.monitors[index].workspace;
+        debug!("Monitor {} selected", index + 1);
     }
 
-    fn on_map_notify(&self, event: &XMapEvent) {
-        trace!("OnMapNotify event: {:#?}", *event);
+    /// Returns the index of the monitor after (or before) the current monitor, wrapping around.
+    fn relative_monitor(&self, forward: bool) -> Option<usize> {
+        let len = self.monitors.len();
+        match len {
+            0 | 1 => None,
+            _ if forward => Some((self.monitor + 1) % len),
+            _ => Some((self.monitor + len - 1) % len),
+        }
     }
 
-    fn on_configure_notify(&self, event: &XConfigureEvent) {
-        trace!("OnConfigureNotify event: {:#?}", *event);
+    /// Fits monitors to the given output geometries, eg. after outputs are added or removed, then
+    /// arranges every monitor's workspace on its output.
+    fn apply_screens(&mut self, screens: Vec<Quad>) {
+        let backend = Rc::clone(&self.backend);
+        fit_monitors(
+            &mut self.workspaces,
+            &mut self.monitors,
+            self.model,
+            &screens,
+            &self.config,
+            &self.layouts,
+        );
+        for monitor in self.monitors.iter() {
+            self.workspaces[monitor.workspace].show(&*backend);
+        }
+
+        self.monitor = self.monitor.min(self.monitors.len() - 1);
+        self.current = self.monitors[self.monitor].workspace;
+        self.focus_current();
+        info!(
+            "Monitors: {:?}",
+            self.monitors.iter().map(|m| m.screen).collect::<Vec<_>>()
+        );
     }
 
-    fn on_key_press(&self, event: &XKeyEvent) {
-        trace!("OnKeyPress event: {:#?}", *event);
+    /// Re-queries outputs after the backend reports a change, eg. a monitor being plugged in.
+    fn on_screen_change(&mut self) {
+        trace!("OnScreenChange event");
+
+        let screens = self.backend.outputs();
+        if screens.iter().ne(self.monitors.iter().map(|m| &m.screen)) {
+            self.apply_screens(screens);
+        }
     }
 
-    fn on_enter_notify(&mut self, event: &XCrossingEvent) {
-        trace!("OnEnterNotify event: {:#?}", *event);
+    /// Gives input focus to the selected (or otherwise first visible) client of the current
+    /// workspace, or to the root window if no client is visible.
+    fn focus_current(&mut self) {
+        let backend = Rc::clone(&self.backend);
+        let root = self.root;
+        let palette = self.palette.clone();
+        let focusable = self.get_current().and_then(Workspace::focusable);
+
+        match (self.get_mut_current(), focusable) {
+            (Some(workspace), Some(index)) => workspace.update_selected(&*backend, &palette, index),
+            _ => backend.focus(root),
+        }
+    }
 
-        /* Cloning for now even though its safe to borrow */
-        let display_copy = self.display;
+    /// Asks a client to close by sending it ```WM_DELETE_WINDOW```, if it lists that protocol in
+    /// ```WM_PROTOCOLS```, so that it may eg. prompt to save changes. Clients that do not take part
+    /// in the protocol are disconnected with ```XKillClient```, as are those still managed
+    /// ```kill_timeout``` later that do not answer a ```_NET_WM_PING``` (ie. have hung).
+    /// Either way, the client is unmanaged once its window is unmapped or destroyed.
+    fn close(&mut self, window: WindowId) {
+        let delete = self.atoms.wm_delete_window;
+
+        if !self.backend.protocols(window).contains(&delete) {
+            debug!(
+                "Client {} does not support WM_DELETE_WINDOW; killing it",
+                window
+            );
+            self.backend.kill_client(window);
+            return;
+        }
+
+        self.backend.send_client_message(
+            window,
+            self.atoms.wm_protocols,
+            &[delete as c_long, CURRENT_TIME],
+        );
+
+        let timeout = self.config.arrangement.kill_timeout;
+        if timeout > 0 && !self.closing.iter().any(|c| c.window == window) {
+            self.closing.push(Closing {
+                window,
+                deadline: Instant::now() + Duration::from_millis(timeout),
+                pinged: false,
+            });
+        }
+    }
+
+    /// Handles clients that were asked to close more than ```kill_timeout``` ago, and are still
+    /// managed: those supporting ```_NET_WM_PING``` are pinged, and given another ```kill_timeout```
+    /// to answer; the rest, and those that did not answer, are disconnected.
+    fn expire_closing(&mut self) {
+        let now = Instant::now();
+        let (expired, pending): (Vec<_>, Vec<_>) =
+            self.closing.drain(..).partition(|c| c.deadline <= now);
+        self.closing = pending;
+
+        let timeout = Duration::from_millis(self.config.arrangement.kill_timeout);
+        let ping = self.atoms.net_wm_ping;
+        for closing in expired {
+            let window = closing.window;
+            if self.find_client(window).is_none() {
+                continue;
+            }
+
+            if !closing.pinged && self.backend.protocols(window).contains(&ping) {
+                debug!("Client {} did not close in time; pinging it", window);
+                self.backend.send_client_message(
+                    window,
+                    self.atoms.wm_protocols,
+                    &[ping as c_long, CURRENT_TIME, window as c_long],
+                );
+                self.closing.push(Closing {
+                    window,
+                    deadline: now + timeout,
+                    pinged: true,
+                });
+            } else {
+                warn!("Client {} did not close in time; killing it", window);
+                self.backend.kill_client(window);
+            }
+        }
+    }
+
+    /// Runs a shell command in the background, detached from Rdwm.
+    fn spawn(&self, action: &str) {
+        if let Err(err) = process::spawn(action, self.backend.connection()) {
+            error!("Could not run {:?}: {}", action, err);
+        }
+    }
+
+    /// Blocks until an X event is queued, a signal arrives, the configuration file changes or a
+    /// client is due to be killed. What woke it is handled by the next loop iteration.
+    fn wait(&self) {
+        /* Pending flushes any buffered requests, which must happen before sleeping */
+        if self.backend.pending() {
+            return;
+        }
 
-        /* Very pythonic but should live elsewhere to prevent duplication */
-        if let Some((num, client)) = self
+        unsafe {
+            let mut fds = [
+                pollfd {
+                    fd: self.backend.connection(),
+                    events: POLLIN,
+                    revents: 0,
+                },
+                pollfd {
+                    fd: self.signals.fd(),
+                    events: POLLIN,
+                    revents: 0,
+                },
+                pollfd {
+                    /* Negative descriptors are ignored by poll */
+                    fd: self.watch.as_ref().map_or(-1, FileWatch::fd),
+                    events: POLLIN,
+                    revents: 0,
+                },
+            ];
+            /* Wake for the earliest client that is due to be killed, if any */
+            let now = Instant::now();
+            let timeout = self
+                .closing
+                .iter()
+                .map(|c| c.deadline.saturating_duration_since(now).as_nanos())
+                .min()
+                .map_or(-1, |nanos| {
+                    nanos.div_ceil(1_000_000).min(c_int::MAX as u128) as c_int
+                });
+            poll(fds.as_mut_ptr(), fds.len() as nfds_t, timeout);
+        }
+    }
+
+    /// Publishes the current workspace's layout symbol in ```LAYOUT_PROPERTY``` on the root window,
+    /// if it changed since last published.
+    fn publish_layout(&mut self) {
+        let symbol = self
             .get_current()
-            .expect("No current")
-            .clients
-            .iter()
-            .enumerate()
-            .find(|(_, c)| c.frame.id == event.window)
-        {
-            trace!("Client: {:#?} Number: {:#?}", client, num);
+            .map(Workspace::symbol)
+            .unwrap_or_default();
+        if symbol != self.layout_symbol {
+            self.backend.set_text_property(
+                self.root,
+                self.atoms.rdwm_layout,
+                self.atoms.utf8_string,
+                &symbol,
+            );
+            self.layout_symbol = symbol;
+        }
+    }
+
+    /// Handles pending signals, and reloads the configuration file if it changed. Neither blocks.
+    fn handle_wakeups(&mut self) {
+        for signal in self.signals.pending() {
+            self.on_signal(signal);
+        }
+
+        if self.watch.as_ref().is_some_and(FileWatch::changed) {
+            info!("Configuration file changed");
+            self.reload();
+        }
+    }
+
+    /// Re-reads and re-validates the configuration file, then re-applies keybindings, borders and
+    /// arrangements to every workspace. The current configuration is kept if the file cannot be
+    /// read, parsed or validated, so that a typo does not leave Rdwm without keybindings.
+    fn reload(&mut self) {
+        let file = match Config::locate(self.config_path.as_deref()) {
+            Some(file) => file,
+            None => {
+                warn!("No configuration file found; keeping current configuration");
+                return;
+            }
+        };
+        if self.watch.as_ref().map(FileWatch::path) != Some(file.as_path()) {
+            self.watch = watch(&file);
+        }
+
+        let (config, diagnostics) = match Config::from_path(&file) {
+            Ok(config) => config.validate(&*self.backend, &self.layouts),
+            Err(err) => {
+                error!("{}; keeping current configuration", err);
+                return;
+            }
+        };
+        if !diagnostics.is_empty() {
+            for diagnostic in diagnostics {
+                error!("{}: {}", file.display(), diagnostic);
+            }
+            error!("Keeping current configuration");
+            return;
+        }
+
+        self.config = EffectiveConfig::resolve(config);
+        self.grab_keys();
+
+        let backend = Rc::clone(&self.backend);
+        let borders = self.config.borders.clone();
+        self.palette.free(&*backend);
+        self.palette = Palette::alloc(&*backend, &borders);
+        let names = &self.config.workspaces.names;
+        let count = match self.model {
+            Model::Workspaces => self.workspaces.len(),
+            Model::Tags => self.tags,
+        };
+        if self.config.workspaces.model != self.model || names.len() != count {
+            warn!("Workspace model or number of workspaces changed; takes effect on restart");
+        }
+        if self.model == Model::Workspaces {
+            for (workspace, name) in self.workspaces.iter_mut().zip(names) {
+                workspace.name = name.clone();
+            }
+        }
+
+        for workspace in self.workspaces.iter_mut() {
+            workspace.configure(&self.config.arrangement, &self.layouts);
+            workspace.redecorate(&*backend, &borders, &self.palette);
+            workspace.arrange(&*backend);
+        }
+
+        info!("Reloaded configuration from {}", file.display());
+    }
+
+    fn on_signal(&mut self, signal: c_int) {
+        trace!("OnSignal: {}", signal);
 
+        match signal {
+            SIGCHLD => process::reap(),
+            SIGHUP => self.reload(),
+            _ => debug!("Ignoring signal {}", signal),
+        }
+    }
+
+    fn on_enter_notify(&mut self, window: WindowId) {
+        trace!("OnEnterNotify event: {:#?}", window);
+
+        /* Cloning for now even though its safe to borrow */
+        let backend = Rc::clone(&self.backend);
+        let palette = self.palette.clone();
+
+        /* Entering a client on another monitor makes that monitor current */
+        let found = self.monitors.iter().enumerate().find_map(|(monitor, m)| {
+            self.workspaces[m.workspace]
+                .clients
+                .iter()
+                .position(|c| c.frame.id == window)
+                .map(|num| (monitor, num))
+        });
+        if let Some((monitor, num)) = found {
+            trace!("Monitor: {:#?} Number: {:#?}", monitor, num);
+
+            self.select_monitor(monitor);
             self.get_mut_current()
                 .expect("No current")
-                .update_selected(display_copy, num);
-        } else {
-            return;
+                .update_selected(&*backend, &palette, num);
         }
     }
 
-    fn on_leave(&self, event: &XCrossingEvent) {
-        trace!("OnLeaveNotify event: {:#?}", *event);
+    fn on_leave(&self, window: WindowId) {
+        trace!("OnLeaveNotify event: {:#?}", window);
     }
 
-    fn on_focus_in(&mut self, event: &XFocusChangeEvent) {
-        trace!("OnFocusIn event: {:#?}", *event);
+    fn on_focus_in(&mut self, window: WindowId) {
+        trace!("OnFocusIn event: {:#?}", window);
     }
 
-    fn on_unmap_notify(&mut self, event: &XUnmapEvent) {
-        trace!("OnUnmapNotify event: {:#?}", *event);
+    fn on_unmap_notify(&mut self, window: WindowId, synthetic: bool) {
+        trace!(
+            "OnUnmapNotify event: {:#?} (synthetic: {})",
+            window,
+            synthetic
+        );
+
+        /* Frames, which Rdwm unmaps itself when hiding a workspace, are not clients so are ignored */
+        let (workspace, num) = match self.find_client(window) {
+            Some(found) => found,
+            None => {
+                trace!("Ignoring UnmapNotify for unmanaged window {}", window);
+                return;
+            }
+        };
 
-        if (*event).event == self.root {
-            info!("Ignoring UnmapNotify for existing window");
+        let client = &mut self.workspaces[workspace].clients[num];
+        if !synthetic && client.ignore_unmaps > 0 {
+            client.ignore_unmaps -= 1;
+            trace!("Ignoring UnmapNotify caused by Rdwm for {}", window);
             return;
         }
 
-        let (num, _) = self
-            .get_current()
-            .expect("No workspaces")
-            .clients
+        /* Either the client unmapped itself, or sent the synthetic UnmapNotify that ICCCM 4.1.4
+         * requires on withdrawing, which is all that arrives if it was already unmapped */
+        self.unmanage(workspace, num, false);
+    }
+
+    /// Returns the workspace and index of the client with the given (client, not frame) window.
+    fn find_client(&self, window: WindowId) -> Option<(usize, usize)> {
+        self.workspaces
             .iter()
             .enumerate()
-            .find(|(_, c)| (*c).context.id == (*event).window)
-            .expect("No such item");
-        {
-            let display = self.display;
-            let root = self.root;
+            .find_map(|(w, workspace)| {
+                workspace
+                    .clients
+                    .iter()
+                    .position(|c| c.context.id == window)
+                    .map(|num| (w, num))
+            })
+    }
 
-            self.get_mut_current()
-                .expect("No such workspace")
-                .destroy_window(display, root, num);
+    /// Stops managing a client that withdrew or was destroyed, then destroys its frame.
+    /// A withdrawn client is moved out of its frame first, into ```WithdrawnState```, as destroying
+    /// the frame would otherwise destroy the client window along with it, and is left where its frame
+    /// was. A destroyed client window is not touched at all.
+    fn unmanage(&mut self, workspace: usize, index: usize, destroyed: bool) {
+        let backend = Rc::clone(&self.backend);
+        let client = self.workspaces[workspace].take(index);
+        debug!(
+            "Unmanaging client {} ({})",
+            client.context.id,
+            if destroyed { "destroyed" } else { "withdrawn" }
+        );
+
+        /* The client may be destroyed at any moment, so errors are expected here */
+        backend.grab_server();
+        if !destroyed {
+            /* Falls back to the workspace's screen, should the frame be gone already */
+            let screen = self.workspaces[workspace].screen;
+            let frame = backend
+                .window_attributes(client.frame.id)
+                .map_or(screen, |frame| frame.area);
+            backend.reparent(
+                client.context.id,
+                self.root,
+                frame.x as c_int,
+                frame.y as c_int,
+            );
+            backend.remove_from_save_set(client.context.id);
+            self.set_state(client.context.id, WITHDRAWN_STATE);
         }
+        backend.destroy_window(client.frame.id);
+        backend.sync();
+        backend.ungrab_server();
+
+        self.closing.retain(|c| c.window != client.context.id);
+        self.workspaces[workspace].arrange(&*backend);
+        if workspace == self.current {
+            self.focus_current();
+        }
+    }
+
+    /// Sets a client's ICCCM ```WM_STATE```, eg. ```NORMAL_STATE``` once managed.
+    fn set_state(&self, window: WindowId, state: c_long) {
+        let wm_state = self.atoms.wm_state;
+        self.backend
+            .set_property(window, wm_state, wm_state, &[state, 0 /* No icon window */]);
     }
 
-    fn on_button_press(&self, event: &XButtonEvent) {
-        trace!("OnButtonPress event: {:#?}", *event);
+    fn on_button_press(&self, window: WindowId) {
+        trace!("OnButtonPress event: {:#?}", window);
     }
 
-    fn on_map_request(&mut self, event: &XMapRequestEvent) {
-        self.frame(&(*event).window, false);
-        info!("OnMapRequest event: {:#?}", *event);
+    fn on_map_request(&mut self, window: WindowId) {
+        self.frame(&window, false);
+        info!("OnMapRequest event: {:#?}", window);
     }
 
     /// Given a client window, create and reparent the client within a top-level frame, setting
     /// appropriate client window hints in the process.
-    fn frame(&mut self, window: &XWindow, already_existing: bool) {
-        /* Safe as XGetWindowAttributes will write _something_ to result, and panic on bad request */
-        let window_attributes = unsafe {
-            let mut attrs = std::mem::MaybeUninit::<XWindowAttributes>::zeroed().assume_init();
-            let ok = XGetWindowAttributes(self.display, *window, &mut attrs);
-
-            trace!("Window attributes: {:#?}", ok);
-            assert!(ok != 0, "Could not acquire window attributes");
-            attrs
+    fn frame(&mut self, window: &WindowId, already_existing: bool) {
+        /* The window may already be gone, eg. if its client exited right after mapping it */
+        let window_attributes = match self.backend.window_attributes(*window) {
+            Some(attrs) => attrs,
+            None => {
+                debug!("Could not acquire window attributes for {}", window);
+                return;
+            }
         };
+        trace!("Window attributes: {:#?}", window_attributes);
 
         if already_existing
-            && (window_attributes.override_redirect != 0
-                || window_attributes.map_state != IsViewable)
+            && (window_attributes.override_redirect
+                || window_attributes.map_state != MapState::Viewable)
         {
             trace!(
                 "Window already exists, map state is not viewable, or override redirect set: {:#?}",
@@ -331,137 +1133,226 @@ impl Rdwm {
         };
 
         /* Cloning for now even though its safe to borrow */
-        let display_copy = self.display;
+        let backend = Rc::clone(&self.backend);
         let root_copy = self.root;
+        let borders = self.config.borders.clone();
+        let palette = self.palette.clone();
 
         self.get_mut_current().unwrap().create_window(
-            display_copy,
+            &*backend,
             &root_copy,
+            &borders,
+            &palette,
             &window_attributes,
-            &window,
+            window,
         );
 
-        unsafe {
-            XAddToSaveSet(self.display, *window);
-        }
+        backend.add_to_save_set(*window);
+        self.set_state(*window, NORMAL_STATE);
 
-        self.get_current()
-            .expect("No current")
-            .arrange(self.display);
+        self.get_current().expect("No current").arrange(&*backend);
     }
 
     /// Configure a client window based on given hints.
-    fn on_configure_request(&self, event: &XConfigureRequestEvent) {
-        info!("OnConfigureRequest event: {:#?}", *event);
-        let mut config = XWindowChanges {
-            x: event.x,
-            y: event.y,
-            width: event.width,
-            height: event.height,
-            border_width: event.border_width,
-            sibling: event.above,
-            stack_mode: event.detail,
-        };
+    fn on_configure_request(&self, window: WindowId, config: &WindowChanges, mask: c_uint) {
+        info!("OnConfigureRequest event: {:#?}", window);
         debug!(
-            "XWindowChanges: {:#?} for Window: {:#?}",
-            config,
-            (*event).window
+            "WindowChanges: {:#?} (mask {:#x}) for Window: {:#?}",
+            config, mask, window
         );
 
-        if let Some(client) = self
-            .get_current()
-            .expect("No current")
-            .clients
-            .iter()
-            .find(|c| c.context.id == (*event).window)
-        {
-            /* re-configure existing frame */
-            unsafe {
-                XConfigureWindow(
-                    self.display,
-                    client.frame.id,
-                    event.value_mask as u32,
-                    &mut config,
+        let backend = Rc::clone(&self.backend);
+        match self.find_client(window) {
+            Some((w, num)) if self.workspaces[w].clients[num].floats() => {
+                /* A floating client places itself: move its frame, and size it within the frame.
+                 * A sibling named by the client is not the frame's sibling, so restacking relative
+                 * to it is ignored; restacking relative to every sibling (eg. Above) applies */
+                let client = &self.workspaces[w].clients[num];
+                let mut frame_mask = CW_X | CW_Y | CW_WIDTH | CW_HEIGHT;
+                if mask & CW_SIBLING == 0 {
+                    frame_mask |= CW_STACK_MODE;
+                }
+                backend.configure(client.frame.id, config, mask & frame_mask);
+                backend.configure(window, config, mask & (CW_WIDTH | CW_HEIGHT));
+            }
+            Some((w, num)) => {
+                /* The layout decides where tiled clients go, so the request is refused, as in dwm's
+                 * configure(): the client is told its current geometry instead */
+                let client = &self.workspaces[w].clients[num];
+                let frame = backend.window_attributes(client.frame.id);
+                let context = backend.window_attributes(client.context.id);
+                if let (Some(frame), Some(context)) = (frame, context) {
+                    let area = Quad {
+                        x: frame.area.x + frame.border_width,
+                        y: frame.area.y + frame.border_width,
+                        ..context.area
+                    };
+                    backend.send_configure_notify(window, area, context.border_width);
+                }
+            }
+            None => {
+                /* Unmanaged windows are configured as requested */
+                backend.configure(window, config, mask);
+                info!(
+                    "Resize window: {:#?} to {{ x: {} y: {} }}",
+                    window, config.width, config.height
                 );
-            };
+            }
         }
-        /* configure client window */
-        unsafe {
-            XConfigureWindow(
-                self.display,
-                event.window,
-                event.value_mask as u32,
-                &mut config,
-            );
+    }
+}
+
+/// Fits monitors (and their workspaces) to the given output geometries, without arranging them.
+/// Removed monitors' clients are orphaned, so move to the first monitor's workspace: in the
+/// workspace model, the emptied workspace is hidden, to be shown on any monitor again; in the tag
+/// model, the monitor's workspace is removed, and its clients are also tagged with the first
+/// monitor's view so that they stay visible. Each new monitor shows a hidden workspace in the
+/// workspace model, or a new workspace of its own in the tag model.
+fn fit_monitors(
+    workspaces: &mut Vec<Workspace>,
+    monitors: &mut Vec<Monitor>,
+    model: Model,
+    screens: &[Quad],
+    config: &EffectiveConfig,
+    layouts: &Layouts,
+) {
+    let count = screens.len().max(1);
+
+    let removed: Vec<Monitor> = monitors.drain(count.min(monitors.len())..).collect();
+    /* Last first, so that the indices of tag model workspaces still to be removed hold */
+    for monitor in removed.into_iter().rev() {
+        let mut orphans = match model {
+            Model::Workspaces => {
+                let workspace = &mut workspaces[monitor.workspace];
+                workspace.shown = false;
+                workspace.selected = 0;
+                std::mem::take(&mut workspace.clients)
+            }
+            Model::Tags => workspaces.remove(monitor.workspace).clients,
         };
+        info!("Moving {} clients off removed monitor", orphans.len());
+
+        if let Some(survivor) = monitors.first().map(|m| &mut workspaces[m.workspace]) {
+            for client in orphans.iter_mut() {
+                client.tags |= survivor.view;
+            }
+            survivor.clients.extend(orphans);
+        }
+    }
+
+    while monitors.len() < count {
+        let hidden = workspaces.iter().position(|w| !w.shown);
+        let workspace = match (model, hidden) {
+            (Model::Workspaces, Some(index)) => index,
+            _ => {
+                let number = workspaces.len();
+                workspaces.push(Workspace::init(
+                    number,
+                    &(number + 1).to_string(),
+                    Quad::default(),
+                    config,
+                    layouts,
+                ));
+                number
+            }
+        };
+        workspaces[workspace].shown = true;
+        monitors.push(Monitor {
+            screen: Quad::default(),
+            workspace,
+        });
+    }
+
+    for (monitor, screen) in monitors.iter_mut().zip(screens) {
+        monitor.screen = *screen;
+        workspaces[monitor.workspace].screen = *screen;
+    }
+}
+
+/// Watches the given configuration file, which need not exist yet. Its directory must exist to be
+/// watched, and is never created: without it, the file is not watched, and may only be reloaded
+/// with SIGHUP.
+fn watch(file: &Path) -> Option<FileWatch> {
+    let dir = file.parent().filter(|dir| !dir.as_os_str().is_empty());
+    if let Some(dir) = dir.filter(|dir| !dir.is_dir()) {
         info!(
-            "Resize window: {:#?} to {{ x: {} y: {} }}",
-            event.window, event.width, event.height
+            "Not watching {}: {} does not exist; reload with SIGHUP",
+            file.display(),
+            dir.display()
         );
+        return None;
     }
 
-    /// Static method to interface with X's error handling routines.
-    /// Currently only handles BadAccess errors raised when, on running Rdwm, another X client exists
-    /// that has registered for substructure redirection (ie. another window manager).
-    pub unsafe extern "C" fn on_wm_detected(
-        _display: *mut Display,
-        _event: *mut XErrorEvent,
-    ) -> c_int {
-        //assert_eq!(
-        //    /* Currently panics with SIGILL, until more errors are handled */
-        //    (*event).error_code,
-        //    BadAccess,
-        //    "Expected BadAccess error code OnWMDetected;
-        //    Error handler not implemented for code: {:#?}",
-        //    Rdwm::err_code_pretty((*event).error_code)
-        //);
-
-        error!("Another window manager detected");
-
-        //let mut detected = WM_DETECTED.lock().unwrap();
-        //*detected = true;
-        0 /* This is ignored */
-    }
-
-    fn err_code_pretty(code: c_uchar) -> &'static str {
-        match code {
-            0 => "Success",
-            1 => "BadRequest",
-            2 => "BadValue",
-            3 => "BadWindow",
-            4 => "BadPixmap",
-            5 => "BadAtom",
-            6 => "BadCursor",
-            7 => "BadFont",
-            8 => "BadMatch",
-            9 => "BadDrawable",
-            10 => "BadAccess",
-            11 => "BadAlloc",
-            12 => "BadColor",
-            13 => "BadGC",
-            14 => "BadIDChoice",
-            15 => "BadName",
-            16 => "BadLength",
-            17 => "BadImplementation",
-            128 => "FirstExtensionError",
-            255 => "LastExtensionError",
-            _ => "Unknown error code",
-        }
-    }
+    FileWatch::open(file)
+        .map_err(|err| warn!("Not watching {}: {}", file.display(), err))
+        .ok()
 }
 
-impl Drop for Rdwm {
-    /// Ensure that when event loop is exited through well-defined behaviour (eg. stack unwinding,
-    /// normal exit or X server requests) that the display handle is closed.
-    fn drop(&mut self) {
-        unsafe {
-            /* Safe because only 1 WM per x server */
-            XCloseDisplay(self.display);
-            info!("Closed display OK");
+/// Reasons Rdwm cannot start managing a display.
+#[derive(Debug)]
+pub enum StartupError {
+    /// No display could be opened, eg. as ```$DISPLAY``` is unset.
+    NoDisplay,
+    /// The display has no screens.
+    NoScreen,
+    /// Another X client (ie. another window manager) redirects the root window's substructure.
+    AnotherWm,
+    /// Signal handlers could not be installed.
+    Signals(std::io::Error),
+    /// Another window manager owns the manager selection, and ```--replace``` was not given.
+    SelectionOwned(String),
+    /// The manager selection could not be taken.
+    Selection(String),
+    /// The replaced window manager did not exit in time.
+    ReplaceTimeout,
+    /// The windows that existed before Rdwm started could not be queried.
+    QueryTree,
+}
+
+impl fmt::Display for StartupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StartupError::NoDisplay => write!(f, "could not open the display; is $DISPLAY set?"),
+            StartupError::NoScreen => write!(f, "the display has no screens"),
+            StartupError::AnotherWm => {
+                write!(
+                    f,
+                    "another window manager is already running on this display"
+                )
+            }
+            StartupError::Signals(err) => write!(f, "could not install signal handlers: {}", err),
+            StartupError::SelectionOwned(name) => write!(
+                f,
+                "another window manager owns {}; use --replace to replace it",
+                name
+            ),
+            StartupError::Selection(name) => write!(f, "could not take ownership of {}", name),
+            StartupError::ReplaceTimeout => {
+                write!(f, "the running window manager did not exit when replaced")
+            }
+            StartupError::QueryTree => write!(f, "could not query the existing windows"),
         }
     }
 }
 
+impl std::error::Error for StartupError {}
+
+#[derive(Debug, Clone, Copy)]
+/// A client that was asked to close, and is killed unless it does so (or answers a ping) in time.
+struct Closing {
+    window: WindowId,
+    deadline: Instant,
+    /// Whether the client has been sent ```_NET_WM_PING```, after the first deadline passed.
+    pinged: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// A physical output, eg. a monitor, showing one workspace.
+struct Monitor {
+    screen: Quad,
+    workspace: usize,
+}
+
 #[derive(Debug)]
 /// Workspaces form the core abstraction over a group of client windows whose arrangements affect
 /// that of their peers (unless they are floating or fixed).
@@ -472,175 +1363,405 @@ struct Workspace {
     // TODO a MxN matrix with client indices better represents
     // the abstraction of window arrangement compared to a hierarchical approach
     number: usize,
+    name: String,
+    shown: bool,
     clients: Vec<Client>,
     selected: usize,
     floating: usize,
     screen: Quad,
+    params: Params,
+    gaps: Gaps,
+    smart_gaps: bool,
+    show_gaps: bool,
+    layouts: Vec<Rc<dyn Layout>>,
+    layout: usize,
+    border_width: c_uint,
+    smart_borders: bool,
+    view: TagMask,
+    previous_view: TagMask,
 }
 
 impl Workspace {
-    /// Create an empty workspace of a given size.
-    fn init(number: usize, screen: Quad) -> Self {
-        Workspace {
+    /// Create an empty, hidden workspace of a given size.
+    fn init(
+        number: usize,
+        name: &str,
+        screen: Quad,
+        config: &EffectiveConfig,
+        layouts: &Layouts,
+    ) -> Self {
+        let mut workspace = Workspace {
             number,
+            name: name.into(),
+            shown: false,
             clients: Vec::new(),
             selected: 0,
             floating: 0,
             screen,
+            params: Params {
+                nmaster: 0,
+                master_ratio: 0.0,
+            },
+            gaps: Gaps::default(),
+            smart_gaps: false,
+            show_gaps: true,
+            layouts: Vec::new(),
+            layout: 0,
+            border_width: config.borders.size as c_uint,
+            smart_borders: config.borders.smart,
+            view: ALL_TAGS,
+            previous_view: ALL_TAGS,
+        };
+        if config.workspaces.model == Model::Tags {
+            workspace.view = 1;
+            workspace.previous_view = 1;
         }
+        workspace.configure(&config.arrangement, layouts);
+        workspace
+    }
+
+    /// (Re-)applies arrangement settings, discarding any changes made at runtime.
+    /// The active layout is kept if it is still listed.
+    fn configure(&mut self, arrangement: &Arrangement, layouts: &Layouts) {
+        let active = self.layouts.get(self.layout).map(|l| l.name());
+
+        self.params = Params {
+            nmaster: arrangement.nmaster,
+            master_ratio: arrangement.master_ratio,
+        };
+        self.gaps = Gaps {
+            inner: arrangement.inner_gap as u32,
+            outer: arrangement.outer_gap as u32,
+        };
+        self.smart_gaps = arrangement.smart_gaps;
+        self.show_gaps = true;
+        self.layouts = layouts.resolve(&arrangement.layouts);
+        self.layout = self
+            .layouts
+            .iter()
+            .position(|l| Some(l.name()) == active)
+            .unwrap_or(0);
     }
 
-    /// Returns a shared reference to the currently selected client.
+    /// Switches to the next configured layout, wrapping around.
+    fn cycle_layout(&mut self) {
+        self.layout = (self.layout + 1) % self.layouts.len().max(1);
+        debug!(
+            "Workspace {} layout: {:?}",
+            self.number,
+            self.layouts.get(self.layout)
+        );
+    }
+
+    /// Returns a shared reference to the currently selected client, if it is in view. A client
+    /// selected before its tags left the view is not acted upon.
     fn get_selected(&self) -> Option<&Client> {
-        self.clients.get(self.selected)
+        let view = self.view;
+        self.clients
+            .get(self.selected)
+            .filter(|c| c.tags & view != 0)
     }
 
-    /// Returns an exclusive reference to the currently selected client.
+    /// Returns an exclusive reference to the currently selected client, if it is in view.
     fn get_mut_selected(&mut self) -> Option<&mut Client> {
-        self.clients.get_mut(self.selected)
+        let view = self.view;
+        self.clients
+            .get_mut(self.selected)
+            .filter(|c| c.tags & view != 0)
     }
 
     /// Update the workspaces currently selected client, including re-decorating window frames.
-    fn update_selected(&mut self, display: *mut Display, index: usize) {
+    fn update_selected(&mut self, backend: &dyn Backend, palette: &Palette, index: usize) {
         // TODO Use the type system to enforce indices belonging to the Client collection.
-        let yellow = 0xEEE8AA;
-        let blue = 0x5f316d;
+        if self.clients.is_empty() {
+            return;
+        }
 
-        unsafe {
-            /* If the index is greater, then it's an unmapped window we don't care about*/
-            self.selected = {
-                if self.clients.len() > self.selected {
-                    trace!(
-                        "Change old border: {:#?}",
-                        XSetWindowBorder(display, self.clients[self.selected].frame.id, blue)
-                    );
-                    index
-                } else {
-                    /* "Sensible" default of MRU window */
-                    self.clients.len() - 1
-                }
-            };
+        if let Some(old) = self.clients.get(self.selected) {
+            backend.set_border_pixel(old.frame.id, palette.normal);
+        }
 
-            trace!(
-                "Set border result: {:#?}",
-                XSetWindowBorder(display, self.clients[self.selected].frame.id, yellow)
+        /* If the index is greater, then it's an unmapped window we don't care about.
+         * "Sensible" default of MRU window */
+        self.selected = index.min(self.clients.len() - 1);
+        self.restack(backend);
+        let selected = &self.clients[self.selected];
+
+        backend.set_border_pixel(selected.frame.id, palette.focus);
+        backend.focus(selected.context.id);
+    }
+
+    /// Orders client frames from top to bottom: floating and fullscreen clients above tiled clients,
+    /// with the selected client on top of its peers. In stacked layouts (eg. monocle) this brings
+    /// the selected client into view.
+    fn restack(&self, backend: &dyn Backend) {
+        let above = |c: &Client| {
+            c.flags
+                .intersects(WindowFlags::FLOATING | WindowFlags::FULLSCREEN)
+        };
+        let selected = self.get_selected();
+        let mut frames: Vec<WindowId> = Vec::with_capacity(self.clients.len());
+
+        for layer in &[true, false] {
+            frames.extend(selected.filter(|c| above(c) == *layer).map(|c| c.frame.id));
+            frames.extend(
+                self.clients
+                    .iter()
+                    .enumerate()
+                    .filter(|(num, c)| above(c) == *layer && *num != self.selected)
+                    .map(|(_, c)| c.frame.id),
             );
         }
+
+        backend.restack(&frames);
     }
 
-    /// Creates a window for an X client.
-    /// The window is registered for substructure redirection, focus change and enter / leave events,
-    fn create_window(
-        &mut self,
-        display: *mut Display,
-        root: &XWindow,
-        attrs: &XWindowAttributes,
-        window: &XWindow,
-    ) {
-        let border_width: c_uint = 3;
-        let border_color: c_ulong = 0x316d4c;
-        let bg_color: c_ulong = 0x5f316d;
+    /// Visible clients that are neither floating nor fullscreen, in order.
+    fn tiled(&self) -> impl Iterator<Item = (usize, &Client)> {
+        self.visible().filter(|(_, c)| {
+            !c.flags
+                .intersects(WindowFlags::FLOATING | WindowFlags::FULLSCREEN)
+        })
+    }
 
-        unsafe {
-            let frame = XCreateSimpleWindow(
-                display,
-                *root,
-                0, //(self.clients.len() * (self.screen.w as usize / 2 * self.clients.len())) as i32
-                0,
-                (self.screen.w / 2) as c_uint,
-                (self.screen.h) as c_uint,
-                border_width,
-                border_color,
-                bg_color,
-            );
+    /// Number of tiled clients hidden behind the selected client, in stacked layouts such as
+    /// monocle. Zero for any other layout.
+    pub fn hidden(&self) -> usize {
+        match self.layouts.get(self.layout) {
+            Some(layout) if layout.stacked() => self.tiled().count().saturating_sub(1),
+            _ => 0,
+        }
+    }
 
-            XSelectInput(
-                display,
-                frame,
-                SubstructureRedirectMask
-                    | SubstructureNotifyMask
-                    | FocusChangeMask
-                    | EnterWindowMask
-                    | LeaveWindowMask,
-            );
+    /// Describes the workspace's arrangement for a bar, eg. ```[3/7]``` in monocle when the third of
+    /// seven tiled clients is focused.
+    pub fn symbol(&self) -> String {
+        let n = self.tiled().count();
+        let selected = self
+            .tiled()
+            .position(|(num, _)| num == self.selected)
+            .unwrap_or(0);
+
+        self.layouts
+            .get(self.layout)
+            .map(|l| l.symbol(n, selected))
+            .unwrap_or_default()
+    }
 
-            XReparentWindow(display, *window, frame, 0, 0);
-            XMapWindow(display, frame);
-            XMapWindow(display, *window);
-            XGrabButton(
-                display,
-                Button1,
-                ShiftMask,
-                *window,
-                0,
-                0,
-                GrabModeSync,
-                GrabModeSync,
-                *window,
-                0x0,
-            );
+    /// Re-applies border widths and colours to every client frame, eg. after a configuration reload.
+    fn redecorate(&mut self, backend: &dyn Backend, borders: &Borders, palette: &Palette) {
+        self.border_width = borders.size as c_uint;
+        self.smart_borders = borders.smart;
+        for (num, client) in self.clients.iter().enumerate() {
+            let colour = if num == self.selected {
+                palette.focus
+            } else {
+                palette.normal
+            };
+            let width = if client.flags.contains(WindowFlags::FULLSCREEN) {
+                0
+            } else {
+                borders.size as c_uint
+            };
 
-            self.clients.push(Client::new(
-                String::from("0"),
-                frame,
-                *window,
-                &attrs,
-                &Quad::from_size(self.screen.h, self.screen.w),
-                WindowFlags::NONE,
-            ));
+            backend.set_border_width(client.frame.id, width);
+            backend.set_border_pixel(client.frame.id, colour);
         }
     }
 
-    /// Destroys an X client window. The window (and its frame) are unmapped and destroyed by X.
-    /// Then, the workspace that the client belongs to is rearranged.
-    fn destroy_window(&mut self, display: *mut Display, root: XWindow, index: usize) {
-        let client = &mut self.clients[index];
+    /// Arranges the workspace, which maps the frame of every visible client.
+    fn show(&mut self, backend: &dyn Backend) {
+        self.shown = true;
+        self.arrange(backend);
+    }
 
-        // TODO
-        unsafe {
-            XUnmapWindow(display, client.context.id);
-            XUnmapWindow(display, client.frame.id);
-            XReparentWindow(display, client.context.id, root, 0, 0);
-            XDestroyWindow(display, client.context.id);
-            XDestroyWindow(display, client.frame.id);
+    /// Unmaps every client frame. Clients themselves are left mapped within their frames.
+    fn hide(&mut self, backend: &dyn Backend) {
+        self.shown = false;
+        for client in self.clients.iter() {
+            backend.unmap(client.frame.id);
+        }
+    }
+
+    /// Removes a client from the workspace without destroying it, eg. to move it to another
+    /// workspace. The selection stays on the same client where possible.
+    fn take(&mut self, index: usize) -> Client {
+        let client = self.clients.remove(index);
+        if index < self.selected || self.selected >= self.clients.len() {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        client
+    }
+
+    /// Returns the index of the client after (or before) the selected client, wrapping around.
+    /// Only visible clients are considered.
+    fn relative(&self, forward: bool) -> Option<usize> {
+        let visible: Vec<usize> = self.visible().map(|(num, _)| num).collect();
+        let len = visible.len();
+        let position = visible.iter().position(|num| *num == self.selected);
+        match (len, position) {
+            (0, _) => None,
+            (_, None) => Some(visible[0]),
+            (_, Some(p)) if forward => Some(visible[(p + 1) % len]),
+            (_, Some(p)) => Some(visible[(p + len - 1) % len]),
+        }
+    }
+
+    /// Clients carrying any of the tags in view, in order. Every client, in the workspace model.
+    fn visible(&self) -> impl Iterator<Item = (usize, &Client)> {
+        let view = self.view;
+        self.clients
+            .iter()
+            .enumerate()
+            .filter(move |(_, c)| c.tags & view != 0)
+    }
+
+    /// The client that should have input focus: the selected client if visible, otherwise the first
+    /// visible client.
+    fn focusable(&self) -> Option<usize> {
+        match self.get_selected() {
+            Some(_) => Some(self.selected),
+            None => self.visible().map(|(num, _)| num).next(),
+        }
+    }
+
+    /// Views the clients carrying any of the given tags, remembering the current view for
+    /// ```workspace previous```. An empty view is ignored.
+    fn view_tags(&mut self, view: TagMask) {
+        if view != 0 && view != self.view {
+            self.previous_view = self.view;
+            self.view = view;
+        }
+    }
+
+    /// Replaces the tags of the selected client. A client must carry at least one tag.
+    fn tag_selected(&mut self, tags: TagMask) {
+        if let Some(client) = self.get_mut_selected().filter(|_| tags != 0) {
+            client.tags = tags;
+        }
+    }
+
+    /// Creates a window for an X client.
+    /// The window is registered for substructure redirection, focus change and enter / leave events,
+    fn create_window(
+        &mut self,
+        backend: &dyn Backend,
+        root: &WindowId,
+        borders: &Borders,
+        palette: &Palette,
+        attrs: &WindowAttributes,
+        window: &WindowId,
+    ) {
+        let area = Quad {
+            w: self.screen.w / 2,
+            ..self.screen
         };
+        let frame = backend.create_window(*root, area, borders.size as u32, palette.normal);
+
+        backend.select_input(
+            frame,
+            SUBSTRUCTURE_REDIRECT_MASK
+                | SUBSTRUCTURE_NOTIFY_MASK
+                | FOCUS_CHANGE_MASK
+                | ENTER_WINDOW_MASK
+                | LEAVE_WINDOW_MASK,
+        );
 
-        self.clients.remove(index);
-        self.arrange(display); // TODO What if a Client is destroyed on a different workspace than
-                               // the currently selected workspace?
+        backend.reparent(*window, frame, 0, 0);
+        backend.map(frame);
+        backend.map(*window);
+        backend.grab_button(*window, BUTTON1, SHIFT_MASK);
+
+        self.clients.push(Client::new(
+            String::from("0"),
+            frame,
+            *window,
+            attrs,
+            &Quad::from_size(self.screen.h, self.screen.w),
+            WindowFlags::NONE,
+        ));
+        /* New clients take the tags currently in view, as in dwm */
+        if let Some(client) = self.clients.last_mut() {
+            client.tags = self.view;
+            /* Reparenting a mapped window unmaps it (eg. windows that existed before Rdwm) */
+            if attrs.map_state != MapState::Unmapped {
+                client.ignore_unmaps += 1;
+            }
+        }
     }
 
     /// Refresh client windows on a workspace to match some arrangement, eg. tiling over the screen
-    /// space.
-    fn arrange(&self, display: *mut Display) {
-        for (num, client) in self.clients.iter().enumerate() {
-            trace!("{{ Num: {:#?} Client: {:#?} }}", num, *client);
-            unsafe {
-                trace!(
-                    "Offset: {:#?}",
-                    ((num) * (*client).frame.attrs.window.w as usize / self.clients.len()) as i32
-                );
-                XMoveResizeWindow(
-                    display,
-                    client.frame.id,
-                    ((num) * (*client).frame.attrs.window.w as usize / self.clients.len()) as i32,
-                    0,
-                    self.screen.w / (self.clients.len() as u32),
-                    self.screen.h,
-                );
+    /// space, less any gaps. Gaps are omitted for a lone tiled client when ```smart_gaps``` is set,
+    /// and borders are omitted for a lone or stacked tiled client when ```smart_borders``` is set.
+    /// Floating clients are left where they are, and fullscreen clients cover the whole screen.
+    /// Hidden workspaces are left alone until next shown, and clients out of view are unmapped.
+    fn arrange(&self, backend: &dyn Backend) {
+        if !self.shown {
+            return;
+        }
+        let tiled: Vec<&Client> = self.tiled().map(|(_, c)| c).collect();
+
+        let gaps = if !self.show_gaps || (self.smart_gaps && tiled.len() == 1) {
+            Gaps::default()
+        } else {
+            self.gaps
+        };
+        let layout = self.layouts.get(self.layout);
+        let cells = match layout {
+            Some(layout) => gaps.apply(layout.as_ref(), self.screen, tiled.len(), &self.params),
+            None => Vec::new(),
+        };
+
+        /* A lone visible client needs no border to tell it apart */
+        let alone = tiled.len() == 1 || layout.is_some_and(|l| l.stacked());
+        let border_width = if self.smart_borders && alone {
+            0
+        } else {
+            self.border_width
+        };
+
+        for (num, (client, cell)) in tiled.iter().zip(cells).enumerate() {
+            trace!(
+                "{{ Num: {:#?} Client: {:#?} Cell: {:#?} }}",
+                num,
+                *client,
+                cell
+            );
+
+            /* Cells include the frame's border, which X draws outside of the window's size */
+            let w = cell.w.saturating_sub(2 * border_width).max(1);
+            let h = cell.h.saturating_sub(2 * border_width).max(1);
+            backend.set_border_width(client.frame.id, border_width);
+            backend.move_resize(client.frame.id, Quad { w, h, ..cell });
+            backend.move_resize(client.context.id, Quad::from_size(h, w));
+            backend.map(client.context.id);
+        }
 
-                XMoveResizeWindow(
-                    display,
+        for client in self.clients.iter() {
+            /* Out of view frames are unmapped, as when hiding a workspace */
+            if client.tags & self.view == 0 {
+                backend.unmap(client.frame.id);
+                continue;
+            }
+
+            if client.flags.contains(WindowFlags::FULLSCREEN) {
+                backend.set_border_width(client.frame.id, 0);
+                backend.move_resize(client.frame.id, self.screen);
+                backend.move_resize(
                     client.context.id,
-                    0,
-                    0,
-                    self.screen.w / (self.clients.len() as u32),
-                    self.screen.h,
+                    Quad::from_size(self.screen.h, self.screen.w),
                 );
-                XMapWindow(display, client.frame.id);
-                XMapWindow(display, client.context.id);
             }
+            backend.map(client.frame.id);
         }
+
+        self.restack(backend);
+        debug!(
+            "Workspace {} arranged: {} ({} hidden)",
+            self.number,
+            self.symbol(),
+            self.hidden()
+        );
     }
 }
 
@@ -652,6 +1773,9 @@ struct Client {
     frame: Window,
     context: Window,
     flags: WindowFlags,
+    tags: TagMask,
+    /// UnmapNotify events that Rdwm caused itself, which do not mean the client withdrew.
+    ignore_unmaps: u32,
 }
 
 impl Client {
@@ -659,9 +1783,9 @@ impl Client {
     /// Create a window that shall be tiled.
     fn tile(
         name: String,
-        frame: XWindow,
-        context: XWindow,
-        hints: &XWindowAttributes,
+        frame: WindowId,
+        context: WindowId,
+        hints: &WindowAttributes,
         attrs: &Quad,
     ) -> Self {
         Client {
@@ -669,14 +1793,16 @@ impl Client {
             frame: Window::new(frame, attrs, hints),
             context: Window::new(context, attrs, hints),
             flags: WindowFlags::TILING,
+            tags: ALL_TAGS,
+            ignore_unmaps: 0,
         }
     }
     /// Create a window that shall be floating.
     fn floating(
         name: String,
-        frame: XWindow,
-        context: XWindow,
-        hints: &XWindowAttributes,
+        frame: WindowId,
+        context: WindowId,
+        hints: &WindowAttributes,
         attrs: &Quad,
     ) -> Self {
         Client {
@@ -684,14 +1810,16 @@ impl Client {
             frame: Window::new(frame, attrs, hints),
             context: Window::new(context, attrs, hints),
             flags: WindowFlags::FLOATING,
+            tags: ALL_TAGS,
+            ignore_unmaps: 0,
         }
     }
     /// Create a window that shall have any flags passed in.
     fn new(
         name: String,
-        frame: XWindow,
-        context: XWindow,
-        hints: &XWindowAttributes,
+        frame: WindowId,
+        context: WindowId,
+        hints: &WindowAttributes,
         attrs: &Quad,
         flags: WindowFlags,
     ) -> Self {
@@ -700,8 +1828,15 @@ impl Client {
             frame: Window::new(frame, attrs, hints),
             context: Window::new(context, attrs, hints),
             flags,
+            tags: ALL_TAGS,
+            ignore_unmaps: 0,
         }
     }
+
+    /// Whether the client decides its own geometry, rather than the workspace's layout.
+    fn floats(&self) -> bool {
+        self.flags.contains(WindowFlags::FLOATING) && !self.flags.contains(WindowFlags::FULLSCREEN)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -710,17 +1845,17 @@ impl Client {
 /// that may be used when toggling between floating / tiling modes) and _actual_ values supplied to
 /// X when mapping and resizing Client windows based on a Workspace.
 struct Window {
-    id: XWindow,
+    id: WindowId,
     hints: Attributes,
     attrs: Attributes,
 }
 
 impl Window {
     /// Create a new Window.
-    fn new(id: XWindow, attrs: &Quad, hints: &XWindowAttributes) -> Self {
+    fn new(id: WindowId, attrs: &Quad, hints: &WindowAttributes) -> Self {
         Window {
             id,
-            hints: Attributes::new(&hints),
+            hints: Attributes::new(hints),
             attrs: Attributes::tiling(attrs),
         }
     }
@@ -735,15 +1870,8 @@ struct Attributes {
 }
 
 impl Attributes {
-    fn new(attrs: &XWindowAttributes) -> Self {
-        Attributes {
-            window: Quad {
-                x: attrs.x as u32,
-                y: attrs.y as u32,
-                h: attrs.height as u32,
-                w: attrs.width as u32,
-            },
-        }
+    fn new(attrs: &WindowAttributes) -> Self {
+        Attributes { window: attrs.area }
     }
 
     fn tiling(attrs: &Quad) -> Self {
@@ -751,30 +1879,447 @@ impl Attributes {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-/// A 4-tuple of integers used to plot a point on a screen as a co-ordinate vector.
-struct Quad {
-    x: u32,
-    y: u32,
-    w: u32,
-    h: u32,
+#[test]
+pub fn fit_monitors_to_screens() {
+    let config = EffectiveConfig::default();
+    let layouts = Layouts::builtin();
+    let left = Quad::from_size(600, 800);
+    let right = Quad { x: 800, ..left };
+    let client = |id| {
+        let hints = WindowAttributes {
+            area: Quad::default(),
+            border_width: 0,
+            override_redirect: false,
+            map_state: MapState::Viewable,
+        };
+        Client::tile(String::new(), id, id + 1, &hints, &Quad::default())
+    };
+
+    /* Workspace model: the second monitor's workspace is emptied and hidden when it is removed */
+    let mut workspaces: Vec<Workspace> = (0..3)
+        .map(|n| Workspace::init(n, &n.to_string(), Quad::default(), &config, &layouts))
+        .collect();
+    let mut monitors = Vec::new();
+    let model = Model::Workspaces;
+    fit_monitors(
+        &mut workspaces,
+        &mut monitors,
+        model,
+        &[left, right],
+        &config,
+        &layouts,
+    );
+    assert_eq!(
+        monitors.iter().map(|m| m.workspace).collect::<Vec<_>>(),
+        [0, 1]
+    );
+    assert_eq!(workspaces[1].screen, right);
+
+    workspaces[1].clients.push(client(10));
+    fit_monitors(
+        &mut workspaces,
+        &mut monitors,
+        model,
+        &[left],
+        &config,
+        &layouts,
+    );
+    assert_eq!(monitors.len(), 1);
+    assert!(!workspaces[1].shown && workspaces[1].clients.is_empty());
+    assert_eq!(workspaces[0].clients[0].frame.id, 10);
+
+    fit_monitors(
+        &mut workspaces,
+        &mut monitors,
+        model,
+        &[left, right],
+        &config,
+        &layouts,
+    );
+    assert_eq!(monitors[1].workspace, 1);
+    assert!(workspaces[1].shown);
+    assert_eq!(workspaces[1].screen, right);
+
+    /* Tag model: the second monitor's clients are tagged so as to be visible on the first */
+    let mut workspaces = Vec::new();
+    let mut monitors = Vec::new();
+    let model = Model::Tags;
+    fit_monitors(
+        &mut workspaces,
+        &mut monitors,
+        model,
+        &[left, right],
+        &config,
+        &layouts,
+    );
+    assert_eq!(workspaces.len(), 2);
+    workspaces[0].view = 1 << 2;
+
+    let mut orphan = client(20);
+    orphan.tags = 1 << 4;
+    workspaces[1].clients.push(orphan);
+    fit_monitors(
+        &mut workspaces,
+        &mut monitors,
+        model,
+        &[left],
+        &config,
+        &layouts,
+    );
+    assert_eq!(workspaces.len(), 1);
+    assert_eq!(workspaces[0].visible().count(), 1);
+    assert_eq!(workspaces[0].clients[0].tags, 1 << 4 | 1 << 2);
 }
 
-impl Quad {
-    fn default() -> Self {
-        Quad {
-            x: 0,
-            y: 0,
-            w: 0,
-            h: 0,
-        }
+#[test]
+pub fn arrange_without_a_display() {
+    let config = EffectiveConfig::default();
+    let layouts = Layouts::builtin();
+    let screen = Quad::from_size(600, 800);
+    let backend = MockBackend::new(screen);
+    let root = backend.root();
+    let palette = Palette::alloc(&backend, &config.borders);
+
+    let mut workspace = Workspace::init(0, "0", screen, &config, &layouts);
+    let windows: Vec<WindowId> = (0..3)
+        .map(|_| {
+            let window = backend.create_window(root, Quad::from_size(100, 100), 0, 0);
+            let attrs = backend.window_attributes(window).unwrap();
+            workspace.create_window(&backend, &root, &config.borders, &palette, &attrs, &window);
+            window
+        })
+        .collect();
+    let frames: Vec<WindowId> = workspace.clients.iter().map(|c| c.frame.id).collect();
+    assert!(windows.iter().zip(&frames).all(|(window, frame)| backend
+        .window(*window)
+        .unwrap()
+        .parent
+        == *frame));
+
+    /* Hidden workspaces are left alone */
+    workspace.hide(&backend);
+    workspace.arrange(&backend);
+    assert!(frames.iter().all(|f| !backend.window(*f).unwrap().mapped));
+
+    /* Tiled: the master on the left, the stack split on the right, all within the screen */
+    workspace.show(&backend);
+    let areas: Vec<Quad> = frames
+        .iter()
+        .map(|f| {
+            let frame = backend.window(*f).unwrap();
+            assert!(frame.mapped);
+            let border = 2 * frame.border_width;
+            assert!(frame.area.x + frame.area.w + border <= screen.w);
+            assert!(frame.area.y + frame.area.h + border <= screen.h);
+            frame.area
+        })
+        .collect();
+    assert!(areas[0].x < areas[1].x && areas[1].x == areas[2].x);
+    assert!(areas[1].y < areas[2].y);
+    assert!(areas[0].h > areas[1].h);
+
+    /* Client windows fill their frames */
+    for (window, area) in windows.iter().zip(&areas) {
+        let client = backend.window(*window).unwrap();
+        assert!(client.mapped);
+        assert_eq!(client.area, Quad::from_size(area.h, area.w));
     }
 
-    fn from_size(h: u32, w: u32) -> Self {
-        Quad { x: 0, y: 0, w, h }
+    /* Out of view clients are unmapped */
+    workspace.clients[2].tags = 0;
+    workspace.arrange(&backend);
+    assert!(!backend.window(frames[2]).unwrap().mapped);
+    assert!(backend.window(frames[0]).unwrap().mapped);
+}
+
+/// An Rdwm managing a ```MockBackend``` with a single 800x600 output, and the given clients (which
+/// are created and mapped in order).
+#[cfg(test)]
+fn mock_rdwm(config: EffectiveConfig, clients: usize) -> (Rc<MockBackend>, Rdwm, Vec<WindowId>) {
+    let backend = Rc::new(MockBackend::new(Quad::from_size(600, 800)));
+    let selection = backend.acquire_selection(false).unwrap();
+    let mut rdwm = Rdwm::new(
+        Rc::clone(&backend) as Rc<dyn Backend>,
+        selection,
+        Signals::ignored(),
+        config,
+        Layouts::builtin(),
+    );
+
+    let windows = (0..clients)
+        .map(|_| {
+            let window = backend.create_window(backend.root(), Quad::from_size(100, 100), 0, 0);
+            rdwm.on_map_request(window);
+            window
+        })
+        .collect();
+    (backend, rdwm, windows)
+}
+
+#[test]
+pub fn selection_out_of_view() {
+    let mut config = EffectiveConfig::default();
+    config.workspaces.model = Model::Tags;
+    let (backend, mut rdwm, windows) = mock_rdwm(config, 2);
+    let selected = rdwm.workspaces[0].selected;
+
+    /* Viewing an empty tag leaves the selection on a client that is now out of view */
+    rdwm.execute(Operation::View(2));
+    for operation in [
+        Operation::KillFocus,
+        Operation::ToggleFloating,
+        Operation::ToggleFullscreen,
+        Operation::SwapMaster,
+        Operation::Tag(3),
+        Operation::ToggleTag(3),
+        Operation::MoveToWorkspace(3),
+    ] {
+        rdwm.execute(operation);
     }
+    assert!(backend.killed.borrow().is_empty());
+    assert!(backend.messages.borrow().is_empty());
+    let workspace = &rdwm.workspaces[0];
+    assert_eq!(workspace.selected, selected);
+    assert!(workspace
+        .clients
+        .iter()
+        .all(|c| c.tags == 1 && c.flags == WindowFlags::NONE));
+    assert_eq!(
+        workspace
+            .clients
+            .iter()
+            .map(|c| c.context.id)
+            .collect::<Vec<_>>(),
+        windows
+    );
+
+    /* Back in view, the selected client is the one closed */
+    rdwm.execute(Operation::View(1));
+    rdwm.execute(Operation::KillFocus);
+    assert_eq!(
+        *backend.killed.borrow(),
+        [rdwm.workspaces[0].clients[selected].context.id]
+    );
+}
 
-    fn from_coords(x: u32, y: u32) -> Self {
-        Quad { x, y, h: 0, w: 0 }
+#[test]
+pub fn configure_requests() {
+    let (backend, mut rdwm, windows) = mock_rdwm(EffectiveConfig::default(), 2);
+    let changes = WindowChanges {
+        x: 10,
+        y: 20,
+        width: 300,
+        height: 200,
+        border_width: 0,
+        sibling: windows[1],
+        stack_mode: 0,
+    };
+    let frame = |window| {
+        let (w, num) = rdwm.find_client(window).unwrap();
+        rdwm.workspaces[w].clients[num].frame.id
+    };
+    let (tiled, floating) = (windows[0], windows[1]);
+    let floating_frame = frame(floating);
+
+    /* Tiled clients are refused */
+    backend.configured.borrow_mut().clear();
+    rdwm.on_configure_request(tiled, &changes, CW_X | CW_WIDTH);
+    assert!(backend.configured.borrow().is_empty());
+
+    /* Floating clients move their frame, but never restack it relative to their own sibling */
+    rdwm.workspaces[0]
+        .clients
+        .iter_mut()
+        .find(|c| c.context.id == floating)
+        .unwrap()
+        .flags = WindowFlags::FLOATING;
+    rdwm.on_configure_request(
+        floating,
+        &changes,
+        CW_X | CW_WIDTH | CW_SIBLING | CW_STACK_MODE,
+    );
+    rdwm.on_configure_request(floating, &changes, CW_Y | CW_STACK_MODE);
+    assert_eq!(
+        *backend.configured.borrow(),
+        [
+            (floating_frame, CW_X | CW_WIDTH),
+            (floating, CW_WIDTH),
+            (floating_frame, CW_Y | CW_STACK_MODE),
+            (floating, 0),
+        ]
+    );
+    assert_eq!(backend.window(floating_frame).unwrap().area.x, 10);
+
+    /* Unmanaged windows are configured as requested */
+    let unmanaged = backend.create_window(backend.root(), Quad::from_size(10, 10), 0, 0);
+    rdwm.on_configure_request(unmanaged, &changes, CW_X | CW_SIBLING);
+    assert_eq!(
+        backend.configured.borrow().last(),
+        Some(&(unmanaged, CW_X | CW_SIBLING))
+    );
+}
+
+#[test]
+pub fn view_and_move_between_workspaces() {
+    let (backend, mut rdwm, windows) = mock_rdwm(EffectiveConfig::default(), 2);
+    let mapped = |rdwm: &Rdwm, window| {
+        let (w, num) = rdwm.find_client(window).unwrap();
+        backend
+            .window(rdwm.workspaces[w].clients[num].frame.id)
+            .unwrap()
+            .mapped
+    };
+    let workspace_of = |rdwm: &Rdwm, window| rdwm.find_client(window).unwrap().0;
+    assert!(windows.iter().all(|w| mapped(&rdwm, *w)));
+
+    /* Viewing another workspace hides the current one, until viewed again */
+    rdwm.execute(Operation::Workspace(2));
+    assert_eq!((rdwm.current, rdwm.previous), (1, 0));
+    assert!(rdwm.workspaces[1].shown && !rdwm.workspaces[0].shown);
+    assert!(windows.iter().all(|w| !mapped(&rdwm, *w)));
+    rdwm.execute(Operation::PreviousWorkspace);
+    assert_eq!((rdwm.current, rdwm.previous), (0, 1));
+    assert!(windows.iter().all(|w| mapped(&rdwm, *w)));
+
+    /* Moving sends the selected client to a hidden workspace, leaving the view be */
+    let selected = rdwm.workspaces[0].get_selected().unwrap().context.id;
+    let other = *windows.iter().find(|w| **w != selected).unwrap();
+    rdwm.execute(Operation::MoveToWorkspace(3));
+    assert_eq!(rdwm.current, 0);
+    assert_eq!(workspace_of(&rdwm, selected), 2);
+    assert!(!mapped(&rdwm, selected) && mapped(&rdwm, other));
+
+    /* Following views the target workspace along with the client */
+    rdwm.execute(Operation::FollowToWorkspace(3));
+    assert_eq!((rdwm.current, rdwm.previous), (2, 0));
+    assert!(rdwm.workspaces[0].clients.is_empty());
+    assert_eq!(workspace_of(&rdwm, other), 2);
+    assert!(windows.iter().all(|w| mapped(&rdwm, *w)));
+    assert_eq!(
+        rdwm.workspaces[2].get_selected().map(|c| c.context.id),
+        Some(other)
+    );
+
+    /* Out of range workspaces are ignored */
+    rdwm.execute(Operation::Workspace(10));
+    rdwm.execute(Operation::MoveToWorkspace(10));
+    assert_eq!(rdwm.current, 2);
+    assert_eq!(rdwm.workspaces[2].clients.len(), 2);
+}
+
+#[test]
+pub fn unmanage_clients() {
+    let (backend, mut rdwm, windows) = mock_rdwm(EffectiveConfig::default(), 3);
+    let frame = |rdwm: &Rdwm, window| {
+        let (w, num) = rdwm.find_client(window).unwrap();
+        rdwm.workspaces[w].clients[num].frame.id
+    };
+
+    /* A withdrawn client is left where its frame was, eg. in the stack rather than at the origin */
+    let withdrawn = windows[1];
+    let withdrawn_frame = frame(&rdwm, withdrawn);
+    let area = backend.window(withdrawn_frame).unwrap().area;
+    assert!(area.x > 0);
+    rdwm.on_unmap_notify(withdrawn, false);
+    assert!(rdwm.find_client(withdrawn).is_none());
+    assert!(backend.window(withdrawn_frame).is_none());
+    let client = backend.window(withdrawn).unwrap();
+    assert_eq!(client.parent, backend.root());
+    assert_eq!((client.area.x, client.area.y), (area.x, area.y));
+
+    /* Frames are not clients: unmapping one (eg. hiding a workspace) unmanages nothing */
+    let kept = frame(&rdwm, windows[0]);
+    rdwm.on_unmap_notify(kept, false);
+    assert_eq!(rdwm.workspaces[0].clients.len(), 2);
+
+    /* A destroyed client's window is not touched, and the remaining client fills the screen */
+    let destroyed = windows[2];
+    let destroyed_frame = frame(&rdwm, destroyed);
+    rdwm.on_destroy_notify(destroyed);
+    assert!(rdwm.find_client(destroyed).is_none());
+    assert!(backend.window(destroyed_frame).is_none());
+    assert_eq!(backend.window(destroyed).unwrap().parent, destroyed_frame);
+    let kept = backend.window(kept).unwrap();
+    assert_eq!(kept.area.w + 2 * kept.border_width, 800);
+}
+
+#[test]
+pub fn close_clients() {
+    let (backend, mut rdwm, windows) = mock_rdwm(EffectiveConfig::default(), 5);
+    let atoms = rdwm.atoms;
+    let (delete, ping) = (atoms.wm_delete_window, atoms.net_wm_ping);
+    let expire = |rdwm: &mut Rdwm| {
+        for closing in rdwm.closing.iter_mut() {
+            closing.deadline = Instant::now();
+        }
+        rdwm.expire_closing();
+    };
+    let (unsupported, deleting, hung, answering, patient) =
+        (windows[0], windows[1], windows[2], windows[3], windows[4]);
+    for (window, protocols) in [
+        (deleting, vec![delete]),
+        (hung, vec![delete, ping]),
+        (answering, vec![delete, ping]),
+        (patient, vec![delete, ping]),
+    ] {
+        backend.protocols.borrow_mut().insert(window, protocols);
+    }
+
+    /* Clients without WM_DELETE_WINDOW are killed straight away */
+    rdwm.close(unsupported);
+    assert_eq!(*backend.killed.borrow(), [unsupported]);
+    assert!(rdwm.closing.is_empty());
+
+    /* Others are asked to close, and killed once the timeout expires, unless they support ping */
+    for window in [deleting, hung, answering] {
+        rdwm.close(window);
     }
+    assert_eq!(
+        *backend.messages.borrow(),
+        [deleting, hung, answering].map(|w| (w, atoms.wm_protocols, vec![delete as c_long, 0]))
+    );
+    backend.messages.borrow_mut().clear();
+    expire(&mut rdwm);
+    assert_eq!(*backend.killed.borrow(), [unsupported, deleting]);
+    assert_eq!(
+        *backend.messages.borrow(),
+        [hung, answering].map(|w| (w, atoms.wm_protocols, vec![ping as c_long, 0, w as c_long]))
+    );
+    assert!(rdwm.closing.iter().all(|c| c.pinged));
+
+    /* A client answering the ping (eg. asking to save changes) is spared; the hung one is not, as
+     * answers only count when sent to the root window */
+    let mut pong = [ping as c_long, 0, answering as c_long, 0, 0];
+    rdwm.on_client_message(rdwm.root, atoms.wm_protocols, &pong);
+    pong[2] = hung as c_long;
+    rdwm.on_client_message(answering, atoms.wm_protocols, &pong);
+    expire(&mut rdwm);
+    assert_eq!(*backend.killed.borrow(), [unsupported, deleting, hung]);
+    assert!(rdwm.closing.is_empty());
+
+    /* A timeout of 0 never kills a client that supports WM_DELETE_WINDOW */
+    rdwm.config.arrangement.kill_timeout = 0;
+    rdwm.close(patient);
+    expire(&mut rdwm);
+    assert_eq!(backend.killed.borrow().len(), 3);
+}
+
+#[test]
+pub fn publish_layout_symbol() {
+    let (backend, mut rdwm, _) = mock_rdwm(EffectiveConfig::default(), 3);
+    let published = |rdwm: &mut Rdwm| {
+        rdwm.publish_layout();
+        let key = (rdwm.root, rdwm.atoms.rdwm_layout);
+        backend.text.borrow().get(&key).cloned()
+    };
+
+    assert_eq!(published(&mut rdwm).as_deref(), Some("[tile]"));
+    rdwm.execute(Operation::CycleLayout);
+    let selected = rdwm.workspaces[0].selected + 1;
+    assert_eq!(published(&mut rdwm), Some(format!("[{}/3]", selected)));
+    rdwm.execute(Operation::FocusNext);
+    assert_eq!(
+        published(&mut rdwm),
+        Some(format!("[{}/3]", selected % 3 + 1))
+    );
 }
@@ -0,0 +1,144 @@
+//! The ICCCM manager selection (ICCCM 2.8), ```WM_S<screen>```, through which window managers learn
+//! of, and hand over to, one another. Taken through Xlib, by ```XlibBackend```.
+use super::backend::{self, WindowId};
+use super::rdwm::StartupError;
+use libc::*;
+use std::ffi::CString;
+use std::time::{Duration, Instant};
+use x11::xlib::*;
+
+/// How long ```--replace``` waits for the current window manager to exit.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often to check whether the current window manager has exited.
+const REPLACE_POLL: Duration = Duration::from_millis(10);
+
+/// Ownership of the ```WM_S<screen>``` selection, held through a window that is never mapped.
+#[derive(Debug)]
+pub struct ManagerSelection {
+    atom: backend::Atom,
+    window: WindowId,
+}
+
+impl ManagerSelection {
+    /// Takes ownership of the selection, and announces it with a ```MANAGER``` client message.
+    /// If another window manager owns the selection, it is only taken with ```replace```, after
+    /// which the previous owner is expected to exit; it must do so before Rdwm may redirect the root
+    /// window's substructure.
+    pub fn acquire(
+        display: *mut Display,
+        root: Window,
+        replace: bool,
+    ) -> Result<Self, StartupError> {
+        let intern = |name: &str| {
+            let name = CString::new(name).expect("Atom names contain no NUL bytes");
+            /* Safe as the name outlives the call, and the display is open */
+            unsafe { XInternAtom(display, name.as_ptr(), false as c_int) }
+        };
+
+        unsafe {
+            let name = format!("WM_S{}", XDefaultScreen(display));
+            let atom = intern(&name);
+
+            /* Grabbed so that the owner cannot exit between being found and being watched */
+            XGrabServer(display);
+            let owner = XGetSelectionOwner(display, atom);
+            if owner != 0 {
+                if !replace {
+                    XUngrabServer(display);
+                    return Err(StartupError::SelectionOwned(name));
+                }
+                XSelectInput(display, owner, StructureNotifyMask);
+            }
+            XUngrabServer(display);
+
+            let window = XCreateSimpleWindow(display, root, -1, -1, 1, 1, 0, 0, 0);
+            XSelectInput(display, window, PropertyChangeMask);
+            let time = timestamp(display, window, atom);
+            XSetSelectionOwner(display, atom, window, time);
+            if XGetSelectionOwner(display, atom) != window {
+                XDestroyWindow(display, window);
+                return Err(StartupError::Selection(name));
+            }
+
+            if owner != 0 {
+                info!("Waiting for the window manager owning {} to exit", name);
+                if !wait_for_destroy(display, owner) {
+                    XDestroyWindow(display, window);
+                    return Err(StartupError::ReplaceTimeout);
+                }
+            }
+
+            let mut message = XClientMessageEvent {
+                type_: ClientMessage,
+                serial: 0,
+                send_event: true as c_int,
+                display,
+                window: root,
+                message_type: intern("MANAGER"),
+                format: 32,
+                data: ClientMessageData::new(),
+            };
+            message.data.set_long(0, time as c_long);
+            message.data.set_long(1, atom as c_long);
+            message.data.set_long(2, window as c_long);
+            XSendEvent(
+                display,
+                root,
+                false as c_int,
+                StructureNotifyMask,
+                &mut XEvent::from(message),
+            );
+
+            info!("Acquired {}", name);
+            Ok(ManagerSelection::new(
+                atom as backend::Atom,
+                window as WindowId,
+            ))
+        }
+    }
+
+    /// The selection, as owned through the given window.
+    pub fn new(atom: backend::Atom, window: WindowId) -> Self {
+        ManagerSelection { atom, window }
+    }
+
+    /// Whether a ```SelectionClear``` event, for the given selection on the given window, means that
+    /// another window manager has taken the selection, ie. that Rdwm should exit.
+    pub fn is_lost(&self, selection: backend::Atom, window: WindowId) -> bool {
+        selection == self.atom && window == self.window
+    }
+}
+
+/// Returns a server timestamp, as ICCCM forbids taking a selection at ```CurrentTime```, by
+/// appending nothing to a property of the selection window and waiting for the resulting event.
+unsafe fn timestamp(display: *mut Display, window: Window, property: Atom) -> Time {
+    XChangeProperty(
+        display,
+        window,
+        property,
+        XA_STRING,
+        8,
+        PropModeAppend,
+        std::ptr::null(),
+        0,
+    );
+
+    let mut event: XEvent = std::mem::MaybeUninit::<XEvent>::zeroed().assume_init();
+    XWindowEvent(display, window, PropertyChangeMask, &mut event);
+    event.property.time
+}
+
+/// Waits for the given window to be destroyed, up to ```REPLACE_TIMEOUT```.
+/// Returns false if it was not.
+unsafe fn wait_for_destroy(display: *mut Display, window: Window) -> bool {
+    let deadline = Instant::now() + REPLACE_TIMEOUT;
+    let mut event: XEvent = std::mem::MaybeUninit::<XEvent>::zeroed().assume_init();
+
+    while Instant::now() < deadline {
+        if XCheckTypedWindowEvent(display, window, DestroyNotify, &mut event) != 0 {
+            return true;
+        }
+        std::thread::sleep(REPLACE_POLL);
+    }
+    false
+}
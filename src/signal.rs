@@ -0,0 +1,102 @@
+use libc::*;
+use std::io;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Write end of the self-pipe, used from within the signal handler.
+static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Turns asynchronous POSIX signals into readable bytes on a pipe (the 'self-pipe trick'), so that
+/// they may be waited upon alongside the X connection and handled synchronously in the event loop,
+/// where it is safe to log, allocate and talk to the X server.
+#[derive(Debug)]
+pub struct Signals {
+    read_fd: c_int,
+}
+
+impl Signals {
+    /// Installs a handler for each of the given signals. Only one instance should exist at a time.
+    pub fn install(signals: &[c_int]) -> io::Result<Self> {
+        let mut fds = [0; 2];
+
+        unsafe {
+            /* Both ends are non-blocking: a full pipe drops signals rather than deadlocking */
+            if pipe2(fds.as_mut_ptr(), O_CLOEXEC | O_NONBLOCK) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            WRITE_FD.store(fds[1], Ordering::SeqCst);
+
+            for signal in signals {
+                let mut action: sigaction = std::mem::zeroed();
+                action.sa_sigaction = Signals::handler as extern "C" fn(c_int) as sighandler_t;
+                action.sa_flags = SA_RESTART | if *signal == SIGCHLD { SA_NOCLDSTOP } else { 0 };
+                sigemptyset(&mut action.sa_mask);
+
+                if sigaction(*signal, &action, std::ptr::null_mut()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+
+        Ok(Signals { read_fd: fds[0] })
+    }
+
+    /// Handles no signals, and installs nothing, so as to leave the process' signals alone in tests.
+    #[cfg(test)]
+    pub fn ignored() -> Self {
+        Signals { read_fd: -1 }
+    }
+
+    /// File descriptor that becomes readable whenever a signal is pending.
+    pub fn fd(&self) -> c_int {
+        self.read_fd
+    }
+
+    /// Returns (and forgets) every signal received since the last call, in order of arrival.
+    pub fn pending(&self) -> Vec<c_int> {
+        let mut signals = Vec::new();
+        let mut buffer = [0u8; 64];
+
+        loop {
+            /* Safe as the buffer outlives the call and its length is passed along */
+            let read = unsafe {
+                read(
+                    self.read_fd,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    buffer.len(),
+                )
+            };
+            if read <= 0 {
+                break;
+            }
+            signals.extend(buffer[..read as usize].iter().map(|s| *s as c_int));
+        }
+
+        signals
+    }
+
+    /// Only async-signal-safe functions may be called here; errno is preserved for the interrupted
+    /// code.
+    extern "C" fn handler(signal: c_int) {
+        unsafe {
+            let errno = *__errno_location();
+            let byte = signal as u8;
+            write(
+                WRITE_FD.load(Ordering::SeqCst),
+                &byte as *const u8 as *const c_void,
+                1,
+            );
+            *__errno_location() = errno;
+        }
+    }
+}
+
+#[test]
+pub fn pending_signals() {
+    let signals = Signals::install(&[SIGUSR1]).unwrap();
+    assert!(signals.pending().is_empty());
+
+    unsafe {
+        raise(SIGUSR1);
+    }
+    assert_eq!(signals.pending(), vec![SIGUSR1]);
+}
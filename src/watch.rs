@@ -0,0 +1,124 @@
+use libc::*;
+use std::ffi::{CStr, CString, OsStr};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Watches a single file for changes with inotify.
+/// The file's directory is watched rather than the file itself, as editors commonly replace a file
+/// (write to a temporary file, then rename it over the original) rather than modify it in place.
+/// Only completed writes and renames are reported, as a newly created file may not be written yet.
+#[derive(Debug)]
+pub struct FileWatch {
+    fd: c_int,
+    path: PathBuf,
+}
+
+impl FileWatch {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let dir = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        unsafe {
+            let fd = inotify_init1(IN_NONBLOCK | IN_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mask = IN_CLOSE_WRITE | IN_MOVED_TO;
+            if inotify_add_watch(fd, dir.as_ptr(), mask) < 0 {
+                let err = io::Error::last_os_error();
+                close(fd);
+                return Err(err);
+            }
+
+            Ok(FileWatch {
+                fd,
+                path: path.to_path_buf(),
+            })
+        }
+    }
+
+    /// File descriptor that becomes readable whenever the watched directory changes.
+    pub fn fd(&self) -> c_int {
+        self.fd
+    }
+
+    /// The watched file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Consumes every pending change, returning whether any of them concern the watched file.
+    pub fn changed(&self) -> bool {
+        let name = self.path.file_name();
+        let mut changed = false;
+        /* inotify_event is aligned to its wd field */
+        let mut buffer = [0u32; 1024];
+        let event_size = std::mem::size_of::<inotify_event>();
+
+        loop {
+            let read = unsafe {
+                read(
+                    self.fd,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    std::mem::size_of_val(&buffer),
+                )
+            };
+            if read <= 0 {
+                break;
+            }
+
+            let bytes =
+                unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, read as usize) };
+            let mut offset = 0;
+            while offset + event_size <= bytes.len() {
+                /* Safe as the kernel only writes whole events, each followed by its name */
+                let event = unsafe { &*(bytes.as_ptr().add(offset) as *const inotify_event) };
+                if event.len > 0 {
+                    let raw = &bytes[offset + event_size..offset + event_size + event.len as usize];
+                    let event_name = CStr::from_bytes_until_nul(raw)
+                        .map(|n| OsStr::from_bytes(n.to_bytes()))
+                        .ok();
+                    changed |= event_name.is_some() && event_name == name;
+                }
+                offset += event_size + event.len as usize;
+            }
+        }
+
+        changed
+    }
+}
+
+impl Drop for FileWatch {
+    fn drop(&mut self) {
+        unsafe {
+            close(self.fd);
+        }
+    }
+}
+
+#[test]
+pub fn watch_file() {
+    let dir = std::env::temp_dir().join(format!("rdwm-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+
+    let watch = FileWatch::open(&path).unwrap();
+    assert!(!watch.changed());
+
+    std::fs::write(dir.join("other.toml"), "").unwrap();
+    assert!(!watch.changed());
+
+    let file = std::fs::File::create(&path).unwrap();
+    assert!(!watch.changed());
+    drop(file);
+    assert!(watch.changed());
+
+    std::fs::write(dir.join("config.toml~"), "").unwrap();
+    std::fs::rename(dir.join("config.toml~"), &path).unwrap();
+    assert!(watch.changed());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
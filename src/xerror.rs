@@ -0,0 +1,163 @@
+//! Decoding of the errors reported by the X server, which Xlib delivers asynchronously (ie. after
+//! the request that caused them) to whichever error handler is installed.
+use libc::*;
+use std::fmt;
+use x11::xlib::*;
+
+/// Core protocol request codes (Xproto.h) of interest to Rdwm, which Xlib does not define.
+pub const X_CHANGE_WINDOW_ATTRIBUTES: u8 = 2;
+pub const X_CONFIGURE_WINDOW: u8 = 12;
+pub const X_GRAB_BUTTON: u8 = 28;
+pub const X_GRAB_KEY: u8 = 33;
+pub const X_SET_INPUT_FOCUS: u8 = 42;
+pub const X_COPY_AREA: u8 = 62;
+pub const X_POLY_SEGMENT: u8 = 66;
+pub const X_POLY_FILL_RECTANGLE: u8 = 70;
+pub const X_POLY_TEXT8: u8 = 74;
+
+/// Names of the requests Rdwm makes, for logging.
+const REQUESTS: &[(u8, &str)] = &[
+    (X_CHANGE_WINDOW_ATTRIBUTES, "ChangeWindowAttributes"),
+    (3, "GetWindowAttributes"),
+    (4, "DestroyWindow"),
+    (6, "ChangeSaveSet"),
+    (7, "ReparentWindow"),
+    (8, "MapWindow"),
+    (10, "UnmapWindow"),
+    (X_CONFIGURE_WINDOW, "ConfigureWindow"),
+    (14, "GetGeometry"),
+    (15, "QueryTree"),
+    (18, "ChangeProperty"),
+    (20, "GetProperty"),
+    (25, "SendEvent"),
+    (X_GRAB_BUTTON, "GrabButton"),
+    (X_GRAB_KEY, "GrabKey"),
+    (34, "UngrabKey"),
+    (X_SET_INPUT_FOCUS, "SetInputFocus"),
+    (X_COPY_AREA, "CopyArea"),
+    (X_POLY_SEGMENT, "PolySegment"),
+    (X_POLY_FILL_RECTANGLE, "PolyFillRectangle"),
+    (X_POLY_TEXT8, "PolyText8"),
+    (113, "KillClient"),
+];
+
+/// Errors that are expected as clients come and go, so are not worth reporting, as in dwm's
+/// ```xerror```: eg. configuring or focusing a window whose client has just exited.
+/// Any ```BadWindow``` error is benign, besides these (request code, error code) pairs.
+const BENIGN: &[(u8, u8)] = &[
+    (X_SET_INPUT_FOCUS, BadMatch),
+    (X_POLY_TEXT8, BadDrawable),
+    (X_POLY_FILL_RECTANGLE, BadDrawable),
+    (X_POLY_SEGMENT, BadDrawable),
+    (X_CONFIGURE_WINDOW, BadMatch),
+    (X_GRAB_BUTTON, BadAccess),
+    (X_GRAB_KEY, BadAccess),
+    (X_COPY_AREA, BadDrawable),
+];
+
+/// An error reported by the X server, decoded from an ```XErrorEvent```.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XError {
+    /// Serial number of the failed request.
+    pub serial: c_ulong,
+    /// Major opcode of the failed request.
+    pub request_code: u8,
+    /// Minor opcode of the failed request, for extension requests.
+    pub minor_code: u8,
+    pub error_code: u8,
+    /// The window, pixmap, atom, etc. that the request referred to, where relevant.
+    pub resource_id: XID,
+}
+
+impl From<&XErrorEvent> for XError {
+    fn from(event: &XErrorEvent) -> Self {
+        XError {
+            serial: event.serial,
+            request_code: event.request_code,
+            minor_code: event.minor_code,
+            error_code: event.error_code,
+            resource_id: event.resourceid,
+        }
+    }
+}
+
+impl XError {
+    /// Whether the error is the result of a known, harmless race with a client, rather than a bug.
+    pub fn is_benign(&self) -> bool {
+        self.error_code == BadWindow || BENIGN.contains(&(self.request_code, self.error_code))
+    }
+
+    /// Whether the error was raised by selecting substructure redirection on the root window while
+    /// another X client (ie. another window manager) has done so.
+    pub fn is_wm_detected(&self) -> bool {
+        self.request_code == X_CHANGE_WINDOW_ATTRIBUTES && self.error_code == BadAccess
+    }
+}
+
+impl fmt::Display for XError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} from ", err_code_pretty(self.error_code))?;
+        match REQUESTS.iter().find(|(code, _)| *code == self.request_code) {
+            Some((_, name)) => write!(f, "{}", name)?,
+            None => write!(f, "request {}.{}", self.request_code, self.minor_code)?,
+        }
+        write!(
+            f,
+            " on resource {:#x} (serial {})",
+            self.resource_id, self.serial
+        )
+    }
+}
+
+/// Names a core protocol error code.
+pub fn err_code_pretty(code: c_uchar) -> &'static str {
+    match code {
+        0 => "Success",
+        1 => "BadRequest",
+        2 => "BadValue",
+        3 => "BadWindow",
+        4 => "BadPixmap",
+        5 => "BadAtom",
+        6 => "BadCursor",
+        7 => "BadFont",
+        8 => "BadMatch",
+        9 => "BadDrawable",
+        10 => "BadAccess",
+        11 => "BadAlloc",
+        12 => "BadColor",
+        13 => "BadGC",
+        14 => "BadIDChoice",
+        15 => "BadName",
+        16 => "BadLength",
+        17 => "BadImplementation",
+        128 => "FirstExtensionError",
+        255 => "LastExtensionError",
+        _ => "Unknown error code",
+    }
+}
+
+#[test]
+pub fn decode_errors() {
+    let error = |request_code, error_code| XError {
+        serial: 42,
+        request_code,
+        minor_code: 0,
+        error_code,
+        resource_id: 0x600003,
+    };
+
+    assert!(error(X_CONFIGURE_WINDOW, BadWindow).is_benign());
+    assert!(error(X_SET_INPUT_FOCUS, BadMatch).is_benign());
+    assert!(!error(X_SET_INPUT_FOCUS, BadValue).is_benign());
+    assert!(error(X_CHANGE_WINDOW_ATTRIBUTES, BadAccess).is_wm_detected());
+    assert!(!error(X_CHANGE_WINDOW_ATTRIBUTES, BadAccess).is_benign());
+
+    assert_eq!(
+        error(X_CONFIGURE_WINDOW, BadWindow).to_string(),
+        "BadWindow from ConfigureWindow on resource 0x600003 (serial 42)"
+    );
+    assert_eq!(
+        error(140, BadValue).to_string(),
+        "BadValue from request 140.0 on resource 0x600003 (serial 42)"
+    );
+}